@@ -1,80 +1,78 @@
-use std::collections::HashSet;
-
-use crate::player::Player;
+use crate::game_camera::{FadeCamera, FadeFinished};
+use crate::interactable::{Interact, Interactable, InteractableKind};
+use crate::map::Location;
+use crate::state::AppState;
 use bevy::prelude::*;
-use bevy_rapier2d::prelude::*;
 
-#[derive(Debug)]
-pub enum DoorEvents {
-    EnteredDoorRange(usize),
-    LeftDoorRagne(usize),
-}
+/// Seconds the screen takes to fade to black going through a door, and back again after
+const FADE_DURATION: f32 = 0.25;
+/// Frames to wait for the player to come out the other side before fading back in anyway
+const ARRIVE_FRAMES: u32 = 10;
 
-struct DoorsInRange(HashSet<usize>);
+/// Sent when the player goes through a door.
+pub struct UseDoor(pub String);
 
-pub struct Door(pub usize);
+/// How far through a door the player is.
+enum Transition {
+    /// Waiting for the screen to fade to black
+    Leaving(String),
+    /// Waiting to come out the other side, from where the player was
+    Arriving { from: Location, frames: u32 },
+}
 
 pub struct DoorPlugin;
 
 impl Plugin for DoorPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_event::<DoorEvents>()
-            .add_startup_system(setup.system())
-            .add_system(system.system())
-            .add_system(display_events.system());
-    }
-}
-
-fn display_events(mut door_events: EventReader<DoorEvents>) {
-    for _door_event in door_events.iter() {
-        // TODO: process option to enter door
-        // println!("Received door event: {:?}", door_event);
+        app.add_event::<UseDoor>()
+            .add_system_set(SystemSet::on_update(AppState::Playing).with_system(system.system()));
     }
 }
 
+/// Fades to black when the player interacts with a door, takes them through it, then fades back
+/// in once they're on the other side.
 fn system(
-    narrow_phase: Res<NarrowPhase>,
-    player_query: Query<Entity, With<Player>>,
-    door_query: Query<(Entity, &Door)>,
-    mut door_events: EventWriter<DoorEvents>,
-    mut doors_in_range_query: Query<&mut DoorsInRange>,
+    mut interact: EventReader<Interact>,
+    mut fade_finished: EventReader<FadeFinished>,
+    mut transition: Local<Option<Transition>>,
+    location: Res<Location>,
+    interactable_query: Query<&Interactable>,
+    mut use_door: EventWriter<UseDoor>,
+    mut fade_camera: EventWriter<FadeCamera>,
 ) {
-    let mut doors_in_range_this_frame = HashSet::<usize>::new();
+    let faded = fade_finished.iter().count() > 0;
 
-    for player_entity in player_query.iter() {
-        for (door_entity, door) in door_query.iter() {
-            if narrow_phase.intersection_pair(player_entity.handle(), door_entity.handle())
-                == Some(true)
-            {
-                doors_in_range_this_frame.insert(door.0);
-            }
+    for Interact(id) in interact.iter() {
+        if transition.is_none()
+            && interactable_query.iter().any(|interactable| {
+                interactable.id == *id && interactable.kind == InteractableKind::Door
+            })
+        {
+            fade_camera.send(FadeCamera::ToBlack(FADE_DURATION));
+            *transition = Some(Transition::Leaving(id.clone()));
         }
     }
 
-    for mut doors_in_range in doors_in_range_query.iter_mut() {
-        // Add doors that weren't in range last frame
-        for door_id in doors_in_range_this_frame.iter() {
-            if !doors_in_range.0.contains(door_id) {
-                doors_in_range.0.insert(*door_id);
-                door_events.send(DoorEvents::EnteredDoorRange(*door_id));
-            }
+    *transition = match transition.take() {
+        Some(Transition::Leaving(id)) if faded => {
+            use_door.send(UseDoor(id));
+            Some(Transition::Arriving {
+                from: location.clone(),
+                frames: 0,
+            })
         }
-        // Remove doors that were in range last frame
-        for door_id in doors_in_range
-            .0
-            .difference(&doors_in_range_this_frame)
-            .into_iter()
-            .cloned()
-            .collect::<Vec<usize>>()
-        {
-            doors_in_range.0.remove(&door_id);
-            door_events.send(DoorEvents::LeftDoorRagne(door_id));
+        Some(Transition::Arriving { from, frames }) => {
+            let arrived = *location != from && !matches!(*location, Location::Entering(_));
+            if arrived || frames >= ARRIVE_FRAMES {
+                fade_camera.send(FadeCamera::FromBlack(FADE_DURATION));
+                None
+            } else {
+                Some(Transition::Arriving {
+                    from,
+                    frames: frames + 1,
+                })
+            }
         }
-    }
-}
-
-fn setup(mut commands: Commands) {
-    commands
-        .spawn()
-        .insert(DoorsInRange(HashSet::<usize>::new()));
+        other => other,
+    };
 }
@@ -1,116 +1,612 @@
+mod effects;
+
 use crate::map;
 use crate::player;
+use crate::settings::Settings;
+use crate::state::AppState;
+use crate::touch::TouchControls;
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub use effects::{
+    CameraCue, CameraEffects, Easing, FadeCamera, FadeFinished, PanCamera, PanFinished, PanTarget,
+    ShakeCamera,
+};
 
 const CAMERA_PADDING: f32 = 48.0;
 const DIAGONAL_PIXELS: f32 = 400.0;
 
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 3.0;
+/// How much each line scrolled zooms by
+const ZOOM_STEP: f32 = 1.1;
+/// Pixels scrolled by touchpads that count as one line
+const PIXELS_PER_LINE: f32 = 20.0;
+
+/// How the world is scaled up to fill the window.
+#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+pub enum Scaling {
+    /// Scale to fill the window, depending on its size
+    Smooth,
+    /// Scale the world up by a whole number so its pixels stay square, with sprites and the
+    /// camera snapped to whole pixels of the world. Shows `internal_resolution` pixels of the world
+    /// with black bars around it, and zooming steps between whole number scales.
+    PixelPerfect,
+}
+
+/// How far the player has zoomed in, on top of the scale for the window.
+pub struct Zoom(pub f32);
+
+impl Default for Zoom {
+    fn default() -> Self {
+        Zoom(1.0)
+    }
+}
+
+/// One of the black bars around the world in pixel perfect mode.
+struct Letterbox;
+
 #[derive(Default)]
-struct GameCamera;
+pub struct GameCamera;
+
+/// Works out where the camera follows the player to, before effects are applied.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, SystemLabel)]
+struct FollowSystem;
+
+/// Marks entities the camera keeps in view, like the players in local co-op.
+pub struct CameraTarget;
+
+/// How the camera follows its targets, centring on the box around them. Effects like shaking are
+/// applied on top by sending `ShakeCamera`, `FadeCamera` and `PanCamera`, rather than moving the
+/// camera directly.
+pub struct CameraController {
+    /// Half the size of the box around the middle of the view the player can move in without
+    /// the camera following
+    pub deadzone: Vec2,
+    /// How quickly the camera catches up with the player, as a rate per second. Higher is
+    /// snappier.
+    pub smoothing: f32,
+    /// Seconds of the player's movement to look ahead by, or 0 to stay on the player
+    pub look_ahead: f32,
+    /// Furthest the camera zooms out to keep every target in view, as a fraction of the zoom
+    pub min_framing: f32,
+    /// How far the camera has zoomed out to keep every target in view
+    framing: f32,
+    /// Where the camera is heading
+    target: Vec2,
+    /// Where the camera follows the player to, before effects
+    position: Vec2,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController {
+            deadzone: Vec2::new(24.0, 16.0),
+            smoothing: 4.0,
+            look_ahead: 0.5,
+            min_framing: 0.5,
+            framing: 1.0,
+            target: Vec2::ZERO,
+            position: Vec2::ZERO,
+        }
+    }
+}
+
+impl CameraController {
+    /// Moves the target just far enough to keep the targets' centre, looking ahead by their
+    /// velocity, inside the deadzone around it.
+    fn follow(&mut self, centre: Vec2, velocity: Vec2) {
+        let focus = centre + velocity * self.look_ahead;
+        let offset = focus - self.target;
+        let outside = offset.abs() - self.deadzone;
+        self.target += offset.signum() * outside.max(Vec2::ZERO);
+    }
+}
 
 pub struct GameCameraPlugin;
 
 impl Plugin for GameCameraPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(setup.system())
-            .add_system_to_stage(CoreStage::PostUpdate, position_camera.system());
+        app.init_resource::<Zoom>()
+            .add_event::<ShakeCamera>()
+            .add_event::<FadeCamera>()
+            .add_event::<FadeFinished>()
+            .add_event::<PanCamera>()
+            .add_event::<PanFinished>()
+            .add_startup_system(setup.system())
+            .add_startup_system(effects::setup.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(zoom.system())
+                    .with_system(effects::fade.system())
+                    .with_system(effects::cue_camera.system()),
+            )
+            .add_system(letterbox.system())
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                snap_sprites
+                    .system()
+                    .after(TransformSystem::TransformPropagate),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(position_camera.system().label(FollowSystem))
+                    .with_system(effects::apply_effects.system().after(FollowSystem)),
+            );
     }
 }
 
-fn calculate_zoom(x: f32, y: f32) -> f32 {
-    let mut scale = (x * x + y * y).sqrt() / DIAGONAL_PIXELS;
+/// How many window pixels each pixel of the world takes up, and the size of the part of the
+/// window the world is shown in.
+fn calculate_zoom(window: &Window, zoom: f32, settings: &Settings) -> (f32, Vec2) {
+    let (x, y) = (window.width(), window.height());
+
+    match settings.scaling {
+        Scaling::Smooth => {
+            let mut scale = (x * x + y * y).sqrt() / DIAGONAL_PIXELS;
+
+            // Scale up in multiples of 1 unless the window is very small
+            if scale > 2.0 {
+                scale = scale.floor();
+            } else if scale > 1.5 {
+                scale = 1.5;
+            } else if scale > 1.0 {
+                scale = 1.0;
+            }
 
-    // Scale up in multiples of 1 unless the window is very small
-    if scale > 2.0 {
-        scale = scale.floor();
-    } else if scale > 1.5 {
-        scale = 1.5;
-    } else if scale > 1.0 {
-        scale = 1.0;
+            (scale * zoom, Vec2::new(x, y))
+        }
+        Scaling::PixelPerfect => {
+            // The largest whole number scale that fits the internal resolution in the window
+            let fit = (x / settings.internal_resolution.x)
+                .min(y / settings.internal_resolution.y)
+                .floor()
+                .max(1.0);
+            // Zooming shows fewer or more pixels of the world in the same space
+            let scale = (fit * zoom).round().max(1.0);
+
+            (
+                scale,
+                (settings.internal_resolution * fit).min(Vec2::new(x, y)),
+            )
+        }
     }
+}
 
-    scale
+/// Zooms in and out with the mouse wheel or by pinching.
+fn zoom(
+    mut mouse_wheel: EventReader<MouseWheel>,
+    touch_controls: Res<TouchControls>,
+    mut zoom: ResMut<Zoom>,
+) {
+    for event in mouse_wheel.iter() {
+        let lines = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        };
+        zoom.0 *= ZOOM_STEP.powf(lines);
+    }
+    if let Some(pinch) = touch_controls.pinch {
+        zoom.0 *= pinch;
+    }
+
+    zoom.0 = zoom.0.clamp(MIN_ZOOM, MAX_ZOOM);
+}
+
+/// Covers the window around the world with black bars in pixel perfect mode.
+fn letterbox(
+    windows: Res<Windows>,
+    zoom: Res<Zoom>,
+    settings: Res<Settings>,
+    controller_query: Query<&CameraController>,
+    mut letterbox_query: Query<&mut Style, With<Letterbox>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let framing = controller_query
+        .iter()
+        .next()
+        .map_or(1.0, |controller| controller.framing);
+    let (_, view) = calculate_zoom(window, zoom.0 * framing, &settings);
+    let bar = (Vec2::new(window.width(), window.height()) - view) / 2.0;
+
+    let corner = |left, bottom| Rect {
+        left: Val::Px(left),
+        bottom: Val::Px(bottom),
+        ..Default::default()
+    };
+    let bars = [
+        // Left, right, bottom and top
+        (Vec2::new(bar.x, window.height()), corner(0.0, 0.0)),
+        (
+            Vec2::new(bar.x, window.height()),
+            corner(window.width() - bar.x, 0.0),
+        ),
+        (Vec2::new(window.width(), bar.y), corner(0.0, 0.0)),
+        (
+            Vec2::new(window.width(), bar.y),
+            corner(0.0, window.height() - bar.y),
+        ),
+    ];
+
+    for (mut style, (size, position)) in letterbox_query.iter_mut().zip(bars) {
+        style.size = Size::new(Val::Px(size.x), Val::Px(size.y));
+        style.position = position;
+    }
+}
+
+/// Lines sprites up with the world's pixels in pixel perfect mode, so none are drawn between
+/// them.
+#[allow(clippy::type_complexity)]
+fn snap_sprites(
+    settings: Res<Settings>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut query_set: QuerySet<(
+        Query<(&Sprite, &mut GlobalTransform)>,
+        Query<(
+            &TextureAtlasSprite,
+            &Handle<TextureAtlas>,
+            &mut GlobalTransform,
+        )>,
+    )>,
+) {
+    if settings.scaling != Scaling::PixelPerfect {
+        return;
+    }
+
+    for (sprite, mut transform) in query_set.q0_mut().iter_mut() {
+        snap(&mut transform, sprite.size);
+    }
+    for (sprite, texture_atlas, mut transform) in query_set.q1_mut().iter_mut() {
+        let rect = texture_atlases
+            .get(texture_atlas)
+            .and_then(|texture_atlas| texture_atlas.textures.get(sprite.index as usize));
+        if let Some(rect) = rect {
+            snap(&mut transform, rect.max - rect.min);
+        }
+    }
+}
+
+fn snap(transform: &mut GlobalTransform, size: Vec2) {
+    let size = size * transform.scale.truncate();
+    let centre = snap_to_pixels(transform.translation.truncate(), size);
+    transform.translation.x = centre.x;
+    transform.translation.y = centre.y;
+}
+
+/// Moves a sprite centred on `centre` so its edges lie on whole pixels.
+fn snap_to_pixels(centre: Vec2, size: Vec2) -> Vec2 {
+    (centre - size / 2.0).round() + size / 2.0
 }
 
+/// Converts a position on the window, like the cursor position, to a position in the world.
+pub fn screen_to_world(window: &Window, camera: &Transform, position: Vec2) -> Vec2 {
+    let offset = position - Vec2::new(window.width(), window.height()) / 2.0;
+    camera.translation.truncate() + offset * camera.scale.truncate()
+}
+
+/// Follows the camera's targets, zooming out to keep them all in view.
 #[allow(clippy::type_complexity)]
 fn position_camera(
     windows: Res<Windows>,
+    time: Res<Time>,
+    zoom: Res<Zoom>,
+    settings: Res<Settings>,
+    active_area: Res<map::ActiveArea>,
     mut query_set: QuerySet<(
-        Query<(&Transform, &player::Player)>,
-        Query<(&GameCamera, &mut Transform)>,
+        Query<(&Transform, &RigidBodyVelocity), With<CameraTarget>>,
+        Query<(&mut CameraController, &mut Transform), With<GameCamera>>,
     )>,
 ) {
     if let Some(window) = windows.get_primary() {
-        let scale = calculate_zoom(window.width(), window.height());
+        let targets = query_set
+            .q0()
+            .iter()
+            .map(|(transform, rigid_body_velocity)| {
+                (
+                    transform.translation.truncate(),
+                    Vec2::new(rigid_body_velocity.linvel.x, rigid_body_velocity.linvel.y),
+                )
+            });
+        let (min, max, velocity) = match frame_targets(targets) {
+            Some(frame) => frame,
+            None => return,
+        };
+        let centre = (min + max) / 2.0;
 
-        // player position
-        let mut pos = Vec3::ZERO;
-        for (transform, _player) in query_set.q0().iter() {
-            pos = transform.translation;
-        }
+        // keep every target at least the padding away from the edge of the window
+        let margin = Vec2::new(player::SPRITE_WIDTH, player::SPRITE_HEIGHT) / 2.0
+            + Vec2::splat(CAMERA_PADDING);
+        let needed = (max - min) / 2.0 + margin;
+
+        for (mut controller, mut cam) in query_set.q1_mut().iter_mut() {
+            let t = smoothing(controller.smoothing, time.delta_seconds());
 
-        for (_camera, mut cam) in query_set.q1_mut().iter_mut() {
+            // zoom out when the targets don't fit, as far as the controller allows
+            let (scale, view) = calculate_zoom(window, zoom.0, &settings);
+            let fit = fit(view / scale / 2.0, needed, controller.min_framing);
+            controller.framing += (fit - controller.framing) * t;
+
+            let (scale, view) = calculate_zoom(window, zoom.0 * controller.framing, &settings);
+            let half_view = view / scale / 2.0;
             cam.scale = Vec3::splat(1.0 / scale);
             cam.scale.z = 1.0;
 
-            // move camera if player out of bounds
-            let horizonal_bound =
-                window.width() / 2.0 - (player::SPRITE_WIDTH / 2.0 + CAMERA_PADDING) * scale;
+            controller.follow(centre, velocity);
+            controller.target = clamp_to_area(controller.target, half_view, &active_area);
 
-            // clip right
-            if (pos.x - cam.translation.x) * scale > horizonal_bound {
-                cam.translation.x = pos.x - horizonal_bound / scale;
-            }
+            let current = controller.position;
+            let position = if current.distance(controller.target) > half_view.length() * 2.0 {
+                // Jump straight to the player when they're teleported, like going through a door
+                controller.target
+            } else {
+                current.lerp(controller.target, t)
+            };
 
-            // clip left
-            if (pos.x - cam.translation.x) * scale < -horizonal_bound {
-                cam.translation.x = pos.x + horizonal_bound / scale;
-            }
+            // never let a target get closer than the padding to the edge of the window, unless
+            // they're too far apart to all fit
+            let bound = (half_view - margin).max(Vec2::ZERO);
+            let position = Vec2::new(
+                keep_between(position.x, max.x - bound.x, min.x + bound.x),
+                keep_between(position.y, max.y - bound.y, min.y + bound.y),
+            );
 
-            let vertical_bound =
-                window.height() / 2.0 - (player::SPRITE_HEIGHT / 2.0 + CAMERA_PADDING) * scale;
+            controller.position = clamp_to_area(position, half_view, &active_area);
+        }
+    }
+}
 
-            // clip top
-            if (pos.y - cam.translation.y) * scale > vertical_bound {
-                cam.translation.y = pos.y - vertical_bound / scale;
-            }
+/// The bottom left and top right of the box around the targets, and their average velocity, from
+/// each target's position and velocity. `None` if there are no targets.
+fn frame_targets(targets: impl Iterator<Item = (Vec2, Vec2)>) -> Option<(Vec2, Vec2, Vec2)> {
+    let mut count = 0;
+    let (mut min, mut max) = (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN));
+    let mut velocity = Vec2::ZERO;
+    for (position, target_velocity) in targets {
+        min = min.min(position);
+        max = max.max(position);
+        velocity += target_velocity;
+        count += 1;
+    }
 
-            // clip bottom
-            if (pos.y - cam.translation.y) * scale < -vertical_bound {
-                cam.translation.y = pos.y + vertical_bound / scale;
-            }
+    if count == 0 {
+        None
+    } else {
+        Some((min, max, velocity / count as f32))
+    }
+}
 
-            // move camera if map out of bounds
-            let map_width = map::MAP_WIDTH as f32;
-            let window_half_width = window.width() / scale / 2.0;
-            // clip right
-            if cam.translation.x + window_half_width > map_width {
-                cam.translation.x = map_width - window_half_width;
-            }
-            // clip left
-            if cam.translation.x < window_half_width {
-                cam.translation.x = window_half_width;
-            }
+/// How much of the zoom to keep so half the view covers `needed`, without zooming in or zooming
+/// out past `min_framing`.
+fn fit(half_view: Vec2, needed: Vec2, min_framing: f32) -> f32 {
+    (half_view / needed).min_element().clamp(min_framing, 1.0)
+}
 
-            let map_height = map::MAP_HEIGHT as f32;
-            let window_half_height = window.height() / scale / 2.0;
-            // clip top
-            if cam.translation.y + window_half_height > map_height {
-                cam.translation.y = map_height - window_half_height;
-            }
-            // clip bottom
-            if cam.translation.y < window_half_height {
-                cam.translation.y = window_half_height;
-            }
-        }
+/// How far to move towards where the camera's heading this frame, as a fraction of the way there.
+/// This is exponential smoothing, which eases in the same way at any frame rate.
+fn smoothing(rate: f32, delta: f32) -> f32 {
+    1.0 - (-rate * delta).exp()
+}
+
+/// Clamps the position between low and high, or goes halfway between them when there's no room.
+fn keep_between(position: f32, low: f32, high: f32) -> f32 {
+    if low > high {
+        (low + high) / 2.0
+    } else {
+        position.clamp(low, high)
     }
 }
 
-fn setup(mut commands: Commands) {
+/// Keeps the view within the area, or centres it on an area smaller than the view. Endless
+/// worlds have no edges to keep within.
+fn clamp_to_area(position: Vec2, half_view: Vec2, active_area: &map::ActiveArea) -> Vec2 {
+    match *active_area {
+        map::ActiveArea::Bounded { min, max } => Vec2::new(
+            clamp_axis(position.x, half_view.x, min.x, max.x),
+            clamp_axis(position.y, half_view.y, min.y, max.y),
+        ),
+        map::ActiveArea::Unbounded => position,
+    }
+}
+
+fn clamp_axis(position: f32, half_view: f32, min: f32, max: f32) -> f32 {
+    if max - min < half_view * 2.0 {
+        (min + max) / 2.0
+    } else {
+        position.clamp(min + half_view, max - half_view)
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    bounds: Res<map::MapBounds>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let black = materials.add(Color::BLACK.into());
+    for _ in 0..4 {
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                material: black.clone(),
+                ..Default::default()
+            })
+            .insert(Letterbox);
+    }
+
     let mut cam = OrthographicCameraBundle::new_2d();
-    let player_pos = Vec3::new(map::MAP_WIDTH / 2.0, map::MAP_HEIGHT / 2.0, 0.0);
+    let player_pos = bounds.centre();
     cam.transform.translation.x = player_pos.x;
     cam.transform.translation.y = player_pos.y;
 
-    commands.spawn_bundle(cam).insert(GameCamera::default());
+    commands
+        .spawn_bundle(cam)
+        .insert(GameCamera)
+        .insert(CameraController {
+            target: player_pos,
+            position: player_pos,
+            ..Default::default()
+        })
+        .insert(CameraEffects::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area() -> map::ActiveArea {
+        map::ActiveArea::Bounded {
+            min: Vec2::ZERO,
+            max: Vec2::new(400.0, 300.0),
+        }
+    }
+
+    #[test]
+    fn smoothing_is_the_same_at_any_frame_rate() {
+        let (from, to) = (Vec2::ZERO, Vec2::new(100.0, 50.0));
+
+        let one_step = from.lerp(to, smoothing(4.0, 0.1));
+        let half_step = smoothing(4.0, 0.05);
+        let two_steps = from.lerp(to, half_step).lerp(to, half_step);
+        assert!(one_step.distance(two_steps) < 1e-4);
+
+        assert_eq!(smoothing(4.0, 0.0), 0.0);
+        assert!(smoothing(4.0, 10.0) > 0.999);
+    }
+
+    #[test]
+    fn target_stays_put_inside_the_deadzone() {
+        let mut controller = CameraController::default();
+
+        controller.follow(Vec2::new(20.0, -10.0), Vec2::ZERO);
+        assert_eq!(controller.target, Vec2::ZERO);
+
+        controller.follow(Vec2::new(30.0, -20.0), Vec2::ZERO);
+        assert_eq!(controller.target, Vec2::new(6.0, -4.0));
+    }
+
+    #[test]
+    fn target_looks_ahead_of_moving_targets() {
+        let mut controller = CameraController {
+            deadzone: Vec2::ZERO,
+            ..Default::default()
+        };
+
+        controller.follow(Vec2::new(10.0, 0.0), Vec2::new(40.0, 0.0));
+        assert_eq!(controller.target, Vec2::new(30.0, 0.0));
+    }
+
+    #[test]
+    fn view_stays_inside_the_area() {
+        let half_view = Vec2::new(100.0, 75.0);
+
+        assert_eq!(
+            clamp_to_area(Vec2::new(50.0, 50.0), half_view, &area()),
+            Vec2::new(100.0, 75.0)
+        );
+        assert_eq!(
+            clamp_to_area(Vec2::new(350.0, 250.0), half_view, &area()),
+            Vec2::new(300.0, 225.0)
+        );
+        assert_eq!(
+            clamp_to_area(Vec2::new(200.0, 150.0), half_view, &area()),
+            Vec2::new(200.0, 150.0)
+        );
+        assert_eq!(
+            clamp_to_area(
+                Vec2::new(-500.0, 0.0),
+                half_view,
+                &map::ActiveArea::Unbounded
+            ),
+            Vec2::new(-500.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn view_centres_on_areas_smaller_than_it() {
+        let half_view = Vec2::new(250.0, 75.0);
+
+        assert_eq!(
+            clamp_to_area(Vec2::new(0.0, 0.0), half_view, &area()),
+            Vec2::new(200.0, 75.0)
+        );
+    }
+
+    #[test]
+    fn frames_the_box_around_every_target() {
+        let targets = vec![
+            (Vec2::new(100.0, 0.0), Vec2::new(10.0, 0.0)),
+            (Vec2::new(0.0, 40.0), Vec2::new(0.0, -4.0)),
+        ];
+
+        let (min, max, velocity) = frame_targets(targets.into_iter()).unwrap();
+        assert_eq!((min + max) / 2.0, Vec2::new(50.0, 20.0));
+        assert_eq!(max - min, Vec2::new(100.0, 40.0));
+        assert_eq!(velocity, Vec2::new(5.0, -2.0));
+        assert!(frame_targets(std::iter::empty()).is_none());
+    }
+
+    #[test]
+    fn zooms_out_to_fit_targets_but_no_further_than_allowed() {
+        let half_view = Vec2::new(200.0, 150.0);
+
+        assert_eq!(fit(half_view, Vec2::new(100.0, 50.0), 0.5), 1.0);
+        assert_eq!(fit(half_view, Vec2::new(100.0, 200.0), 0.5), 0.75);
+        assert_eq!(fit(half_view, Vec2::new(1000.0, 50.0), 0.5), 0.5);
+    }
+
+    #[test]
+    fn targets_too_far_apart_share_the_view_equally() {
+        assert_eq!(keep_between(5.0, 0.0, 10.0), 5.0);
+        assert_eq!(keep_between(-5.0, 0.0, 10.0), 0.0);
+        assert_eq!(keep_between(5.0, 30.0, 10.0), 20.0);
+    }
+
+    #[test]
+    fn sprites_snap_to_whole_pixels() {
+        assert_eq!(
+            snap_to_pixels(Vec2::new(10.3, 20.6), Vec2::new(4.0, 4.0)),
+            Vec2::new(10.0, 21.0)
+        );
+        // Odd sizes are centred between pixels
+        assert_eq!(
+            snap_to_pixels(Vec2::new(10.2, 20.0), Vec2::new(5.0, 3.0)),
+            Vec2::new(10.5, 20.5)
+        );
+    }
+
+    #[test]
+    fn pixel_perfect_zoom_steps_between_whole_scales() {
+        let window = Window::new(
+            bevy::window::WindowId::primary(),
+            &WindowDescriptor::default(),
+            1280,
+            720,
+            1.0,
+            None,
+        );
+        let settings = Settings {
+            scaling: Scaling::PixelPerfect,
+            internal_resolution: Vec2::new(384.0, 216.0),
+            ..Default::default()
+        };
+
+        let (scale, view) = calculate_zoom(&window, 1.0, &settings);
+        assert_eq!(scale, 3.0);
+        assert_eq!(view, Vec2::new(1152.0, 648.0));
+
+        // The view stays the same size however far it's zoomed
+        assert_eq!(calculate_zoom(&window, 1.1, &settings), (3.0, view));
+        assert_eq!(calculate_zoom(&window, 1.2, &settings), (4.0, view));
+        assert_eq!(calculate_zoom(&window, 0.5, &settings), (2.0, view));
+        assert_eq!(calculate_zoom(&window, 0.1, &settings).0, 1.0);
+    }
 }
@@ -0,0 +1,471 @@
+use super::{calculate_zoom, clamp_to_area, CameraController, GameCamera, Scaling, Zoom};
+use crate::interactable::{EnteredRange, InRange, Interactable, LeftRange};
+use crate::map;
+use crate::settings::Settings;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// How much trauma wears off each second
+const TRAUMA_DECAY: f32 = 1.0;
+/// Furthest the camera moves from where it should be at full trauma, in world pixels
+const MAX_SHAKE_OFFSET: f32 = 12.0;
+/// Furthest the camera turns at full trauma, in radians
+const MAX_SHAKE_ANGLE: f32 = 0.05;
+/// How quickly the camera shakes back and forth
+const SHAKE_SPEED: f64 = 40.0;
+
+/// Sent to shake the camera by adding trauma, from 0 to 1. The camera shakes with the square of
+/// its trauma, so small knocks barely register but add up.
+pub struct ShakeCamera(pub f32);
+
+/// Sent to fade the screen to or from black over a number of seconds.
+pub enum FadeCamera {
+    ToBlack(f32),
+    FromBlack(f32),
+}
+
+/// Sent once the screen has finished fading.
+pub struct FadeFinished;
+
+/// Where the camera pans to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PanTarget {
+    /// Holds the camera on a point in the world until it pans somewhere else
+    Point(Vec2),
+    /// Goes back to following the player
+    Player,
+}
+
+/// How a pan speeds up and slows down.
+#[derive(Clone, Copy)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    fn ease(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Sent to move the camera away from the player over a number of seconds, or back to them.
+#[derive(Clone, Copy)]
+pub struct PanCamera {
+    pub to: PanTarget,
+    pub duration: f32,
+    pub easing: Easing,
+}
+
+/// Sent once the camera has finished panning, with where it panned to.
+pub struct PanFinished(pub PanTarget);
+
+/// How the camera reacts to a player walking into a trigger.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum CameraCue {
+    /// Shakes the camera with this much trauma
+    Shake(f32),
+    /// Pans over `duration` seconds to look at a point, then back to the player once every player
+    /// has left. In catalogues the point is relative to the bottom left of the object's sprite.
+    Look { at: Vec2, duration: f32 },
+}
+
+impl CameraCue {
+    /// Moves the cue's point from relative to `origin` into the world.
+    pub fn placed_at(self, origin: Vec2) -> Self {
+        match self {
+            CameraCue::Look { at, duration } => CameraCue::Look {
+                at: origin + at,
+                duration,
+            },
+            shake => shake,
+        }
+    }
+}
+
+/// Effects on top of the camera following the player.
+#[derive(Default)]
+pub struct CameraEffects {
+    trauma: f32,
+    focus: Focus,
+    /// Where the camera was last frame, before shaking
+    position: Vec2,
+}
+
+#[derive(Clone, Copy, Default)]
+enum Focus {
+    #[default]
+    Player,
+    Panning {
+        from: Vec2,
+        pan: PanCamera,
+        elapsed: f32,
+    },
+    Holding(Vec2),
+}
+
+impl Focus {
+    /// Moves on by `delta` seconds, returning where the camera is now, what it's focused on next,
+    /// and where it panned to if a pan just finished. `follow` is where the camera follows the
+    /// player to, and `clamp` keeps points inside the map.
+    fn advance(
+        self,
+        delta: f32,
+        follow: Vec2,
+        clamp: impl Fn(Vec2) -> Vec2,
+    ) -> (Vec2, Focus, Option<PanTarget>) {
+        match self {
+            Focus::Player => (follow, Focus::Player, None),
+            Focus::Holding(point) => {
+                let point = clamp(point);
+                (point, Focus::Holding(point), None)
+            }
+            Focus::Panning { from, pan, elapsed } => {
+                let elapsed = elapsed + delta;
+                let to = match pan.to {
+                    PanTarget::Point(point) => clamp(point),
+                    // The player may have moved, so pan to wherever the camera follows them to
+                    PanTarget::Player => follow,
+                };
+
+                if elapsed >= pan.duration {
+                    let focus = match pan.to {
+                        PanTarget::Point(_) => Focus::Holding(to),
+                        PanTarget::Player => Focus::Player,
+                    };
+                    (to, focus, Some(pan.to))
+                } else {
+                    let t = pan.easing.ease(elapsed / pan.duration);
+                    (
+                        from.lerp(to, t),
+                        Focus::Panning { from, pan, elapsed },
+                        None,
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// The black overlay the screen fades to.
+#[derive(Default)]
+pub(super) struct FadeOverlay(Option<Fade>);
+
+struct Fade {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+pub(super) fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(FadeOverlay::default());
+}
+
+/// Fades the overlay's opacity towards black or clear.
+pub(super) fn fade(
+    time: Res<Time>,
+    mut fade_camera: EventReader<FadeCamera>,
+    mut fade_finished: EventWriter<FadeFinished>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut overlay_query: Query<(&mut FadeOverlay, &Handle<ColorMaterial>, &mut Visible)>,
+) {
+    let requested = fade_camera.iter().last().map(|event| match *event {
+        FadeCamera::ToBlack(duration) => (1.0, duration),
+        FadeCamera::FromBlack(duration) => (0.0, duration),
+    });
+
+    for (mut overlay, material, mut visible) in overlay_query.iter_mut() {
+        let material = match materials.get_mut(material) {
+            Some(material) => material,
+            None => continue,
+        };
+
+        if let Some((to, duration)) = requested {
+            overlay.0 = Some(Fade {
+                from: material.color.a(),
+                to,
+                duration,
+                elapsed: 0.0,
+            });
+        }
+
+        if let Some(fade) = &mut overlay.0 {
+            fade.elapsed += time.delta_seconds();
+            let t = if fade.duration > 0.0 {
+                (fade.elapsed / fade.duration).min(1.0)
+            } else {
+                1.0
+            };
+            material.color.set_a(fade.from + (fade.to - fade.from) * t);
+
+            if t >= 1.0 {
+                overlay.0 = None;
+                fade_finished.send(FadeFinished);
+            }
+        }
+        visible.is_visible = material.color.a() > 0.0;
+    }
+}
+
+/// Shakes or pans the camera as players walk in and out of triggers with camera cues.
+pub(super) fn cue_camera(
+    mut entered_range: EventReader<EnteredRange>,
+    mut left_range: EventReader<LeftRange>,
+    cue_query: Query<(&Interactable, &CameraCue)>,
+    in_range_query: Query<&InRange>,
+    mut shake_camera: EventWriter<ShakeCamera>,
+    mut pan_camera: EventWriter<PanCamera>,
+) {
+    let cue = |id: &str| {
+        cue_query
+            .iter()
+            .find(|(interactable, _)| interactable.id == id)
+            .map(|(_, cue)| *cue)
+    };
+
+    for EnteredRange { id, .. } in entered_range.iter() {
+        match cue(id) {
+            Some(CameraCue::Shake(trauma)) => shake_camera.send(ShakeCamera(trauma)),
+            Some(CameraCue::Look { at, duration }) => pan_camera.send(PanCamera {
+                to: PanTarget::Point(at),
+                duration,
+                easing: Easing::EaseInOut,
+            }),
+            None => {}
+        }
+    }
+
+    for LeftRange { id, .. } in left_range.iter() {
+        let still_inside = in_range_query
+            .iter()
+            .any(|in_range| in_range.0.contains(id));
+        if let (Some(CameraCue::Look { duration, .. }), false) = (cue(id), still_inside) {
+            pan_camera.send(PanCamera {
+                to: PanTarget::Player,
+                duration,
+                easing: Easing::EaseInOut,
+            });
+        }
+    }
+}
+
+/// Moves the camera from where it follows the player to wherever it's panning, and shakes it.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn apply_effects(
+    windows: Res<Windows>,
+    time: Res<Time>,
+    zoom: Res<Zoom>,
+    settings: Res<Settings>,
+    active_area: Res<map::ActiveArea>,
+    mut shake_camera: EventReader<ShakeCamera>,
+    mut pan_camera: EventReader<PanCamera>,
+    mut pan_finished: EventWriter<PanFinished>,
+    mut camera_query: Query<
+        (&CameraController, &mut CameraEffects, &mut Transform),
+        With<GameCamera>,
+    >,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let delta = time.delta_seconds();
+
+    let trauma: f32 = shake_camera.iter().map(|ShakeCamera(trauma)| trauma).sum();
+    let pan = pan_camera.iter().last().copied();
+
+    for (controller, mut effects, mut cam) in camera_query.iter_mut() {
+        let (scale, view) = calculate_zoom(window, zoom.0 * controller.framing, &settings);
+        let half_view = view / scale / 2.0;
+
+        if let Some(pan) = pan {
+            let from = match effects.focus {
+                Focus::Player => controller.position,
+                _ => effects.position,
+            };
+            effects.focus = Focus::Panning {
+                from,
+                pan,
+                elapsed: 0.0,
+            };
+        }
+
+        let (position, focus, finished) =
+            effects.focus.advance(delta, controller.position, |point| {
+                clamp_to_area(point, half_view, &active_area)
+            });
+        if let Some(to) = finished {
+            pan_finished.send(PanFinished(to));
+        }
+        effects.focus = focus;
+        effects.position = position;
+
+        effects.trauma = (effects.trauma + trauma).min(1.0);
+        let shake = effects.trauma * effects.trauma;
+        effects.trauma = (effects.trauma - TRAUMA_DECAY * delta).max(0.0);
+
+        let t = time.seconds_since_startup() * SHAKE_SPEED;
+        let mut position =
+            position + Vec2::new(wobble(t, 0.0), wobble(t, 1.0)) * MAX_SHAKE_OFFSET * shake;
+        let mut angle = wobble(t, 2.0) * MAX_SHAKE_ANGLE * shake;
+        if settings.scaling == Scaling::PixelPerfect {
+            // Line the world's pixels up with the window's, which turning would undo
+            position = position.round();
+            angle = 0.0;
+        }
+
+        cam.translation.x = position.x;
+        cam.translation.y = position.y;
+        cam.rotation = Quat::from_rotation_z(angle);
+    }
+}
+
+/// Smoothly moves back and forth between -1 and 1, differently for each seed.
+fn wobble(t: f64, seed: f64) -> f32 {
+    let seed = seed * 17.0;
+    (((t + seed).sin() + (t * 2.3 + seed * 1.7).sin() * 0.5) / 1.5) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interactable::InteractableKind;
+    use bevy::app::{Events, ManualEventReader};
+
+    fn pan(to: PanTarget) -> Focus {
+        Focus::Panning {
+            from: Vec2::ZERO,
+            pan: PanCamera {
+                to,
+                duration: 1.0,
+                easing: Easing::Linear,
+            },
+            elapsed: 0.0,
+        }
+    }
+
+    #[test]
+    fn easing_starts_and_ends_in_place() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.ease(0.0), 0.0);
+            assert_eq!(easing.ease(1.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn pans_to_a_point_and_holds_there() {
+        let to = Vec2::new(10.0, 0.0);
+
+        let (position, focus, finished) = pan(PanTarget::Point(to)).advance(0.5, Vec2::ZERO, |p| p);
+        assert_eq!(position, Vec2::new(5.0, 0.0));
+        assert_eq!(finished, None);
+
+        let (position, focus, finished) = focus.advance(0.5, Vec2::ZERO, |p| p);
+        assert_eq!(position, to);
+        assert_eq!(finished, Some(PanTarget::Point(to)));
+        assert!(matches!(focus, Focus::Holding(point) if point == to));
+
+        let (position, _, finished) = focus.advance(0.5, Vec2::ZERO, |p| p);
+        assert_eq!(position, to);
+        assert_eq!(finished, None);
+    }
+
+    #[test]
+    fn pans_back_to_the_player_and_follows_them() {
+        let follow = Vec2::new(0.0, 20.0);
+
+        let (position, focus, finished) = pan(PanTarget::Player).advance(1.0, follow, |p| p);
+        assert_eq!(position, follow);
+        assert_eq!(finished, Some(PanTarget::Player));
+        assert!(matches!(focus, Focus::Player));
+    }
+
+    #[test]
+    fn points_are_kept_inside_the_map() {
+        let clamp = |point: Vec2| point.min(Vec2::splat(4.0));
+
+        let (position, focus, _) =
+            pan(PanTarget::Point(Vec2::splat(10.0))).advance(1.0, Vec2::ZERO, clamp);
+        assert_eq!(position, Vec2::splat(4.0));
+        assert!(matches!(focus, Focus::Holding(point) if point == Vec2::splat(4.0)));
+    }
+
+    #[test]
+    fn triggers_pan_the_camera_there_and_back() {
+        let mut app = App::build();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<EnteredRange>()
+            .add_event::<LeftRange>()
+            .add_event::<ShakeCamera>()
+            .add_event::<PanCamera>()
+            .add_system(cue_camera.system());
+        let mut app = app.app;
+        let player = app.world.spawn().insert(InRange::default()).id();
+        app.world
+            .spawn()
+            .insert(Interactable {
+                id: "lookout/0".to_string(),
+                kind: InteractableKind::Trigger,
+                prompt: None,
+            })
+            .insert(
+                CameraCue::Look {
+                    at: Vec2::ZERO,
+                    duration: 1.0,
+                }
+                .placed_at(Vec2::new(8.0, 16.0)),
+            );
+        let mut reader = ManualEventReader::<PanCamera>::default();
+        let mut pans = |app: &App| {
+            let events = app.world.get_resource::<Events<PanCamera>>().unwrap();
+            reader.iter(events).map(|pan| pan.to).collect::<Vec<_>>()
+        };
+
+        app.world
+            .get_resource_mut::<Events<EnteredRange>>()
+            .unwrap()
+            .send(EnteredRange {
+                player,
+                id: "lookout/0".to_string(),
+            });
+        app.update();
+        assert_eq!(pans(&app), [PanTarget::Point(Vec2::new(8.0, 16.0))]);
+
+        app.world
+            .get_resource_mut::<Events<LeftRange>>()
+            .unwrap()
+            .send(LeftRange {
+                player,
+                id: "lookout/0".to_string(),
+            });
+        app.update();
+        assert_eq!(pans(&app), [PanTarget::Player]);
+    }
+}
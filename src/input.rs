@@ -0,0 +1,413 @@
+use std::collections::HashSet;
+
+use crate::settings::Settings;
+use crate::touch::TouchControls;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Something the player can do, which gameplay reads from `Input<Action>` instead of the keys
+/// bound to it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Interact,
+    Pause,
+    Save,
+    Load,
+}
+
+const ACTIONS: [Action; 8] = [
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::Interact,
+    Action::Pause,
+    Action::Save,
+    Action::Load,
+];
+
+/// Stick positions closer to the centre than this are ignored, so worn sticks don't drift
+pub const DEFAULT_STICK_DEADZONE: f32 = 0.2;
+
+/// The keys or gamepad buttons bound to each action. Actions left out of the settings keep their
+/// defaults.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(
+    default,
+    bound(deserialize = "T: Deserialize<'de>, Bindings<T>: Default")
+)]
+pub struct Bindings<T> {
+    pub move_up: Vec<T>,
+    pub move_down: Vec<T>,
+    pub move_left: Vec<T>,
+    pub move_right: Vec<T>,
+    pub interact: Vec<T>,
+    pub pause: Vec<T>,
+    pub save: Vec<T>,
+    pub load: Vec<T>,
+}
+
+impl Default for Bindings<KeyCode> {
+    fn default() -> Self {
+        Bindings {
+            move_up: vec![KeyCode::W, KeyCode::Up],
+            move_down: vec![KeyCode::S, KeyCode::Down],
+            move_left: vec![KeyCode::A, KeyCode::Left],
+            move_right: vec![KeyCode::D, KeyCode::Right],
+            interact: vec![KeyCode::E],
+            pause: vec![KeyCode::Escape],
+            save: vec![KeyCode::F5],
+            load: vec![KeyCode::F9],
+        }
+    }
+}
+
+impl Default for Bindings<GamepadButtonType> {
+    fn default() -> Self {
+        Bindings {
+            move_up: vec![GamepadButtonType::DPadUp],
+            move_down: vec![GamepadButtonType::DPadDown],
+            move_left: vec![GamepadButtonType::DPadLeft],
+            move_right: vec![GamepadButtonType::DPadRight],
+            interact: vec![GamepadButtonType::South],
+            pause: vec![GamepadButtonType::Start],
+            save: Vec::new(),
+            load: Vec::new(),
+        }
+    }
+}
+
+impl Bindings<KeyCode> {
+    /// Keys on the other side of the keyboard, for the second player in local co-op.
+    pub fn second_player() -> Self {
+        Bindings {
+            move_up: vec![KeyCode::I],
+            move_down: vec![KeyCode::K],
+            move_left: vec![KeyCode::J],
+            move_right: vec![KeyCode::L],
+            interact: vec![KeyCode::O],
+            pause: Vec::new(),
+            save: Vec::new(),
+            load: Vec::new(),
+        }
+    }
+}
+
+/// Bindings as written in the settings, where actions can be left out.
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+struct PartialBindings<T> {
+    #[serde(default, deserialize_with = "present")]
+    move_up: Option<Vec<T>>,
+    #[serde(default, deserialize_with = "present")]
+    move_down: Option<Vec<T>>,
+    #[serde(default, deserialize_with = "present")]
+    move_left: Option<Vec<T>>,
+    #[serde(default, deserialize_with = "present")]
+    move_right: Option<Vec<T>>,
+    #[serde(default, deserialize_with = "present")]
+    interact: Option<Vec<T>>,
+    #[serde(default, deserialize_with = "present")]
+    pause: Option<Vec<T>>,
+    #[serde(default, deserialize_with = "present")]
+    save: Option<Vec<T>>,
+    #[serde(default, deserialize_with = "present")]
+    load: Option<Vec<T>>,
+}
+
+/// Reads an action's bindings when they're written out, as `Option` fields would otherwise
+/// need to be written as `Some([..])`.
+fn present<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<T>>, D::Error> {
+    Vec::deserialize(deserializer).map(Some)
+}
+
+impl<T> PartialBindings<T> {
+    fn or(self, defaults: Bindings<T>) -> Bindings<T> {
+        Bindings {
+            move_up: self.move_up.unwrap_or(defaults.move_up),
+            move_down: self.move_down.unwrap_or(defaults.move_down),
+            move_left: self.move_left.unwrap_or(defaults.move_left),
+            move_right: self.move_right.unwrap_or(defaults.move_right),
+            interact: self.interact.unwrap_or(defaults.interact),
+            pause: self.pause.unwrap_or(defaults.pause),
+            save: self.save.unwrap_or(defaults.save),
+            load: self.load.unwrap_or(defaults.load),
+        }
+    }
+}
+
+/// Reads the second player's keys, keeping their own defaults for actions left out rather than
+/// the first player's.
+pub fn deserialize_second_player<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Bindings<KeyCode>, D::Error> {
+    PartialBindings::deserialize(deserializer)
+        .map(|bindings| bindings.or(Bindings::second_player()))
+}
+
+impl<T> Bindings<T> {
+    pub fn get(&self, action: Action) -> &[T] {
+        match action {
+            Action::MoveUp => &self.move_up,
+            Action::MoveDown => &self.move_down,
+            Action::MoveLeft => &self.move_left,
+            Action::MoveRight => &self.move_right,
+            Action::Interact => &self.interact,
+            Action::Pause => &self.pause,
+            Action::Save => &self.save,
+            Action::Load => &self.load,
+        }
+    }
+}
+
+/// The way the player wants to move, with a length of up to 1 for full speed. Sticks can ask
+/// for less than full speed.
+#[derive(Default)]
+pub struct Movement(pub Vec2);
+
+/// Input for the second player in local co-op, from the co-op keys and their gamepad.
+#[derive(Default)]
+pub struct Coop {
+    pub actions: Input<Action>,
+    pub movement: Vec2,
+}
+
+/// Gamepads that are plugged in.
+#[derive(Default)]
+pub struct Gamepads(HashSet<Gamepad>);
+
+impl Gamepads {
+    pub fn iter(&self) -> impl Iterator<Item = &Gamepad> {
+        self.0.iter()
+    }
+
+    /// Splits the gamepads between the first and second player. The second player gets the one
+    /// at `coop_gamepad`, counting in the order they were connected.
+    pub fn assign(&self, coop_gamepad: usize) -> (Vec<Gamepad>, Vec<Gamepad>) {
+        let mut connected: Vec<Gamepad> = self.0.iter().copied().collect();
+        connected.sort_by_key(|gamepad| gamepad.0);
+        let coop_gamepad = connected.get(coop_gamepad).copied();
+        connected
+            .into_iter()
+            .partition(|gamepad| Some(*gamepad) != coop_gamepad)
+    }
+}
+
+/// Updates `Input<Action>` and `Movement`, so input from touch controls is read before it.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, SystemLabel)]
+pub struct ActionSystem;
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Input<Action>>()
+            .init_resource::<Movement>()
+            .init_resource::<Coop>()
+            .init_resource::<Gamepads>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_actions
+                    .system()
+                    .label(ActionSystem)
+                    .after(InputSystem),
+            );
+    }
+}
+
+fn connect_gamepads(mut gamepad_events: EventReader<GamepadEvent>, gamepads: &mut Gamepads) {
+    for GamepadEvent(gamepad, event_type) in gamepad_events.iter() {
+        match event_type {
+            GamepadEventType::Connected => {
+                info!("Connected {:?}", gamepad);
+                gamepads.0.insert(*gamepad);
+            }
+            GamepadEventType::Disconnected => {
+                gamepads.0.remove(gamepad);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The keys, gamepads and touch controls one player uses.
+struct Controls<'a> {
+    bindings: &'a Bindings<KeyCode>,
+    gamepads: &'a [Gamepad],
+    touch: Option<&'a TouchControls>,
+}
+
+/// Presses and releases each player's actions as the keys or buttons bound to them are, and works
+/// out which way they want to move.
+#[allow(clippy::too_many_arguments)]
+fn update_actions(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_events: EventReader<GamepadEvent>,
+    mut gamepads: ResMut<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    touch_controls: Res<TouchControls>,
+    settings: Res<Settings>,
+    mut actions: ResMut<Input<Action>>,
+    mut movement: ResMut<Movement>,
+    mut coop: ResMut<Coop>,
+) {
+    connect_gamepads(gamepad_events, &mut gamepads);
+    let (gamepads, coop_gamepads) = gamepads.assign(settings.coop_gamepad);
+
+    let first_player = Controls {
+        bindings: &settings.bindings,
+        gamepads: &gamepads,
+        touch: Some(&touch_controls),
+    };
+    update_player(
+        first_player,
+        &keyboard_input,
+        &gamepad_buttons,
+        &axes,
+        &settings,
+        &mut actions,
+        &mut movement.0,
+    );
+
+    let coop = &mut *coop;
+    let second_player = Controls {
+        bindings: &settings.coop_bindings,
+        gamepads: &coop_gamepads,
+        touch: None,
+    };
+    update_player(
+        second_player,
+        &keyboard_input,
+        &gamepad_buttons,
+        &axes,
+        &settings,
+        &mut coop.actions,
+        &mut coop.movement,
+    );
+}
+
+fn update_player(
+    controls: Controls,
+    keyboard_input: &Input<KeyCode>,
+    gamepad_buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+    settings: &Settings,
+    actions: &mut Input<Action>,
+    movement: &mut Vec2,
+) {
+    actions.update();
+
+    for action in ACTIONS {
+        let key_pressed = controls
+            .bindings
+            .get(action)
+            .iter()
+            .any(|key| keyboard_input.pressed(*key));
+        let button_pressed = controls.gamepads.iter().any(|gamepad| {
+            settings
+                .gamepad_bindings
+                .get(action)
+                .iter()
+                .any(|button| gamepad_buttons.pressed(GamepadButton(*gamepad, *button)))
+        });
+        let touch_pressed =
+            action == Action::Interact && controls.touch.is_some_and(|touch| touch.interact);
+        let pressed = key_pressed || button_pressed || touch_pressed;
+
+        if pressed && !actions.pressed(action) {
+            actions.press(action);
+        } else if !pressed && actions.pressed(action) {
+            actions.release(action);
+        }
+    }
+
+    *movement = controls
+        .gamepads
+        .iter()
+        .map(|gamepad| left_stick(axes, *gamepad, settings.stick_deadzone))
+        .chain(controls.touch.map(|touch| touch.movement))
+        .find(|movement| *movement != Vec2::ZERO)
+        .unwrap_or_else(|| digital_movement(actions));
+}
+
+/// The left stick's position, rescaled so it starts from zero at the edge of the deadzone.
+fn left_stick(axes: &Axis<GamepadAxis>, gamepad: Gamepad, deadzone: f32) -> Vec2 {
+    let axis = |axis_type| {
+        axes.get(GamepadAxis(gamepad, axis_type))
+            .unwrap_or_default()
+    };
+    let stick = Vec2::new(
+        axis(GamepadAxisType::LeftStickX),
+        axis(GamepadAxisType::LeftStickY),
+    );
+
+    let length = stick.length();
+    if length <= deadzone {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - deadzone) / (1.0 - deadzone)).min(1.0);
+    stick / length * scaled
+}
+
+/// Full speed in the direction of the movement actions held, including diagonals.
+fn digital_movement(actions: &Input<Action>) -> Vec2 {
+    let axis = |negative, positive| match (actions.pressed(negative), actions.pressed(positive)) {
+        (true, false) => -1.0,
+        (false, true) => 1.0,
+        _ => 0.0,
+    };
+    Vec2::new(
+        axis(Action::MoveLeft, Action::MoveRight),
+        axis(Action::MoveDown, Action::MoveUp),
+    )
+    .normalize_or_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_can_be_unbound() {
+        let bindings: Bindings<KeyCode> = ron::from_str("(save: [], load: [F5])").unwrap();
+
+        assert!(bindings.get(Action::Save).is_empty());
+        assert_eq!(bindings.get(Action::Load), &[KeyCode::F5]);
+        assert_eq!(bindings.get(Action::Pause), &[KeyCode::Escape]);
+    }
+
+    #[test]
+    fn movement_actions_walk_at_full_speed() {
+        let mut actions = Input::default();
+        actions.press(Action::MoveUp);
+        actions.press(Action::MoveRight);
+        let movement = digital_movement(&actions);
+        assert!((movement.length() - 1.0).abs() < 1e-6);
+        assert!(movement.x > 0.0 && movement.y > 0.0);
+
+        actions.press(Action::MoveLeft);
+        assert_eq!(digital_movement(&actions), Vec2::Y);
+    }
+
+    #[test]
+    fn left_stick_starts_from_the_edge_of_the_deadzone() {
+        let gamepad = Gamepad(0);
+        let mut axes = Axis::default();
+        let mut push = |x| {
+            axes.set(GamepadAxis(gamepad, GamepadAxisType::LeftStickX), x);
+            left_stick(&axes, gamepad, 0.2)
+        };
+
+        assert_eq!(push(0.1), Vec2::ZERO);
+        assert!((push(0.6).x - 0.5).abs() < 1e-6);
+        assert_eq!(push(1.0), Vec2::X);
+    }
+}
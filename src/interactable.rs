@@ -0,0 +1,370 @@
+use std::collections::HashSet;
+
+use crate::game_camera::{self, GameCamera};
+use crate::input::{Action, Bindings, Coop, Gamepads};
+use crate::player::{self, Player, SecondPlayer};
+use crate::preload::Preload;
+use crate::settings::Settings;
+use crate::state::AppState;
+use crate::window;
+use bevy::prelude::*;
+use bevy::text::Text2dSize;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+const PROMPT_FONT: &str = "fonts/monogram.ttf";
+const PROMPT_FONT_SIZE: f32 = 16.0;
+/// Gap between the top of an interactable and its prompt
+const PROMPT_MARGIN: f32 = 8.0;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum InteractableKind {
+    Door,
+    Sign,
+    Npc,
+    Chest,
+    /// Reacts to the player entering and leaving, without a prompt
+    Trigger,
+}
+
+impl InteractableKind {
+    fn default_prompt(&self) -> Option<&'static str> {
+        match self {
+            InteractableKind::Door => Some("enter"),
+            InteractableKind::Sign => Some("read"),
+            InteractableKind::Npc => Some("talk"),
+            InteractableKind::Chest => Some("open"),
+            InteractableKind::Trigger => None,
+        }
+    }
+}
+
+/// A sensor the player can interact with while they're inside it.
+pub struct Interactable {
+    /// Stable ID, scoped by the object the interactable belongs to
+    pub id: String,
+    pub kind: InteractableKind,
+    /// Shown as "Press <interact key> to <prompt>". Defaults to the usual action for the kind.
+    pub prompt: Option<String>,
+}
+
+impl Interactable {
+    fn prompt(&self) -> Option<&str> {
+        match self.kind {
+            InteractableKind::Trigger => None,
+            _ => self
+                .prompt
+                .as_deref()
+                .or_else(|| self.kind.default_prompt()),
+        }
+    }
+}
+
+/// Sent when a player moves inside an interactable.
+pub struct EnteredRange {
+    pub player: Entity,
+    pub id: String,
+}
+
+/// Sent when a player moves out of an interactable.
+pub struct LeftRange {
+    pub player: Entity,
+    pub id: String,
+}
+
+/// Sent when a player interacts with an interactable in their range.
+pub struct Interact(pub String);
+
+/// The interactables each player is inside.
+#[derive(Default)]
+pub struct InRange(pub HashSet<String>);
+
+/// The prompt shown above an interactable in a player's range.
+struct Prompt {
+    player: Entity,
+    id: String,
+}
+
+struct PromptFont(Handle<Font>);
+
+pub struct InteractablePlugin;
+
+impl Plugin for InteractablePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<EnteredRange>()
+            .add_event::<LeftRange>()
+            .add_event::<Interact>()
+            .add_startup_system(setup.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(system.system())
+                    .with_system(show_prompts.system())
+                    .with_system(interact.system()),
+            );
+    }
+}
+
+/// Shows a prompt above interactables as they come into each player's range, in the player's
+/// colour with their interact key, and hides it once they're out of range.
+#[allow(clippy::too_many_arguments)]
+fn show_prompts(
+    mut commands: Commands,
+    mut entered_range: EventReader<EnteredRange>,
+    mut left_range: EventReader<LeftRange>,
+    prompt_font: Res<PromptFont>,
+    settings: Res<Settings>,
+    gamepads: Res<Gamepads>,
+    touches: Res<Touches>,
+    mut touched: Local<bool>,
+    player_query: Query<Option<&SecondPlayer>, With<Player>>,
+    interactable_query: Query<(&Interactable, &ColliderShape, &ColliderPosition)>,
+    prompt_query: Query<(Entity, &Prompt)>,
+) {
+    // Once the player has used the touch screen, prompt them to tap instead
+    *touched |= touches.iter().next().is_some();
+
+    for EnteredRange { player, id } in entered_range.iter() {
+        let second_player = match player_query.get(*player) {
+            Ok(second_player) => second_player.is_some(),
+            Err(_) => continue,
+        };
+
+        let found = interactable_query
+            .iter()
+            .find(|(interactable, ..)| interactable.id == *id);
+
+        if let Some((interactable, shape, position)) = found {
+            let centre = Vec2::new(
+                position.0.translation.vector.x,
+                position.0.translation.vector.y,
+            );
+            let prompt = match interactable.prompt() {
+                Some(prompt) => prompt,
+                None => continue,
+            };
+
+            let mut top = shape.compute_local_aabb().half_extents().y + PROMPT_MARGIN;
+            let (gamepads, coop_gamepads) = gamepads.assign(settings.coop_gamepad);
+            let (press, color) = if second_player {
+                // Above the first player's prompt, so both can be seen
+                top += PROMPT_FONT_SIZE;
+                (
+                    press_interact(&settings.coop_bindings, &settings, &coop_gamepads, false),
+                    player::SECOND_PLAYER_COLOR,
+                )
+            } else {
+                (
+                    press_interact(&settings.bindings, &settings, &gamepads, *touched),
+                    Color::WHITE,
+                )
+            };
+            let text = format!("{} to {}", press, prompt);
+
+            commands
+                .spawn_bundle(Text2dBundle {
+                    text: Text::with_section(
+                        text,
+                        TextStyle {
+                            font: prompt_font.0.clone(),
+                            font_size: PROMPT_FONT_SIZE,
+                            color,
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Bottom,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    transform: Transform::from_translation(
+                        // In front of everything on the map
+                        (centre + Vec2::new(0.0, top)).extend(20.0),
+                    ),
+                    ..Default::default()
+                })
+                .insert(Prompt {
+                    player: *player,
+                    id: id.clone(),
+                });
+        }
+    }
+
+    for LeftRange { player, id } in left_range.iter() {
+        for (entity, prompt) in prompt_query.iter() {
+            if prompt.player == *player && prompt.id == *id {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+/// How a player interacts, from the key bound to it, and the gamepad button too while they have a
+/// gamepad plugged in.
+fn press_interact(
+    bindings: &Bindings<KeyCode>,
+    settings: &Settings,
+    gamepads: &[Gamepad],
+    touched: bool,
+) -> String {
+    if touched {
+        return "Tap".to_string();
+    }
+
+    let key = bindings
+        .get(Action::Interact)
+        .first()
+        .map(|key| format!("{:?}", key));
+    let button = settings
+        .gamepad_bindings
+        .get(Action::Interact)
+        .first()
+        .filter(|_| !gamepads.is_empty())
+        .map(|button| format!("{:?}", button));
+
+    match (key, button) {
+        (Some(key), Some(button)) => format!("Press {}/{}", key, button),
+        (Some(pressed), None) | (None, Some(pressed)) => format!("Press {}", pressed),
+        (None, None) => "Click".to_string(),
+    }
+}
+
+/// Interacts with an interactable in a player's range when they press their interact key, or
+/// when it or the first player's prompt is clicked or tapped.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn interact(
+    windows: Res<Windows>,
+    actions: Res<Input<Action>>,
+    coop: Res<Coop>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    camera_query: Query<&Transform, With<GameCamera>>,
+    interactable_query: Query<(&Interactable, &ColliderShape, &ColliderPosition)>,
+    prompt_query: Query<(&Prompt, &Transform, &Text2dSize)>,
+    in_range_query: Query<(Entity, &InRange, Option<&SecondPlayer>), With<Player>>,
+    mut interact: EventWriter<Interact>,
+) {
+    for (player, in_range, second_player) in in_range_query.iter() {
+        let in_range_with_prompt = |id: &String| {
+            in_range.0.contains(id)
+                && interactable_query.iter().any(|(interactable, ..)| {
+                    interactable.id == *id && interactable.prompt().is_some()
+                })
+        };
+
+        let actions = match second_player {
+            Some(_) => &coop.actions,
+            None => &*actions,
+        };
+        if actions.just_pressed(Action::Interact) {
+            if let Some(id) = in_range
+                .0
+                .iter()
+                .filter(|id| in_range_with_prompt(id))
+                .min()
+            {
+                interact.send(Interact(id.clone()));
+            }
+            continue;
+        }
+
+        // The mouse and touch screen belong to the first player
+        if second_player.is_some() {
+            continue;
+        }
+
+        let (window, camera) = match (windows.get_primary(), camera_query.iter().next()) {
+            (Some(window), Some(camera)) => (window, camera),
+            _ => continue,
+        };
+
+        let mut presses = Vec::new();
+        if mouse_buttons.just_pressed(MouseButton::Left) {
+            presses.extend(window.cursor_position());
+        }
+        presses.extend(
+            touches
+                .iter_just_pressed()
+                .map(|touch| window::touch_position(window, touch)),
+        );
+
+        for press in presses {
+            let press = game_camera::screen_to_world(window, camera, press);
+
+            let interactable_pressed =
+                interactable_query
+                    .iter()
+                    .find_map(|(interactable, shape, position)| {
+                        if in_range_with_prompt(&interactable.id)
+                            && shape.contains_point(&position.0, &point![press.x, press.y])
+                        {
+                            Some(interactable.id.clone())
+                        } else {
+                            None
+                        }
+                    });
+
+            // Prompts are anchored at their bottom centre
+            let prompt_pressed = prompt_query.iter().find_map(|(prompt, transform, size)| {
+                let offset = press - transform.translation.truncate();
+                if prompt.player == player
+                    && offset.x.abs() <= size.size.width / 2.0
+                    && offset.y >= 0.0
+                    && offset.y <= size.size.height
+                {
+                    Some(prompt.id.clone())
+                } else {
+                    None
+                }
+            });
+
+            if let Some(id) = interactable_pressed.or(prompt_pressed) {
+                interact.send(Interact(id));
+                break;
+            }
+        }
+    }
+}
+
+/// Keeps track of the interactables each player is inside.
+fn system(
+    narrow_phase: Res<NarrowPhase>,
+    interactable_query: Query<(Entity, &Interactable)>,
+    mut entered_range: EventWriter<EnteredRange>,
+    mut left_range: EventWriter<LeftRange>,
+    mut player_query: Query<(Entity, &mut InRange), With<Player>>,
+) {
+    for (player, mut in_range) in player_query.iter_mut() {
+        let in_range_this_frame: HashSet<String> = interactable_query
+            .iter()
+            .filter(|(interactable_entity, _)| {
+                narrow_phase.intersection_pair(player.handle(), interactable_entity.handle())
+                    == Some(true)
+            })
+            .map(|(_, interactable)| interactable.id.clone())
+            .collect();
+
+        // Add interactables that weren't in range last frame
+        for id in in_range_this_frame.iter() {
+            if !in_range.0.contains(id) {
+                in_range.0.insert(id.clone());
+                entered_range.send(EnteredRange {
+                    player,
+                    id: id.clone(),
+                });
+            }
+        }
+        // Remove interactables that were in range last frame
+        for id in in_range
+            .0
+            .difference(&in_range_this_frame)
+            .cloned()
+            .collect::<Vec<String>>()
+        {
+            in_range.0.remove(&id);
+            left_range.send(LeftRange { player, id });
+        }
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut preload: ResMut<Preload>) {
+    preload.load(&asset_server, PROMPT_FONT);
+    commands.insert_resource(PromptFont(asset_server.load(PROMPT_FONT)));
+}
@@ -1,8 +1,18 @@
 pub mod door;
 pub mod game_camera;
+pub mod input;
+pub mod interactable;
 pub mod map;
 pub mod music;
+pub mod nav;
+pub mod params;
 pub mod player;
+pub mod preload;
+pub mod save;
+pub mod settings;
+pub mod state;
+pub mod storage;
+pub mod touch;
 pub mod window;
 pub mod z;
 
@@ -19,16 +29,25 @@ fn main() {
 
     #[cfg(target_arch = "wasm32")]
     app.add_plugin(bevy_webgl2::WebGL2Plugin)
-        .add_plugin(crate::window::WebFullscreenPlugin);
+        .add_plugin(crate::window::WebFullscreenPlugin)
+        .add_plugin(crate::window::WebTouchPlugin);
 
     app.add_plugin(bevy_kira_audio::AudioPlugin)
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
         .add_plugin(RapierRenderPlugin)
+        .add_plugin(settings::SettingsPlugin)
+        .add_plugin(input::InputPlugin)
+        .add_plugin(touch::TouchPlugin)
+        .add_plugin(state::StatePlugin)
+        .add_plugin(preload::PreloadPlugin)
         .add_plugin(music::MusicPlugin)
         .add_plugin(game_camera::GameCameraPlugin)
         .add_plugin(map::MapPlugin)
         .add_plugin(player::PlayerPlugin)
+        .add_plugin(nav::NavPlugin)
+        .add_plugin(interactable::InteractablePlugin)
         .add_plugin(door::DoorPlugin)
+        .add_plugin(save::SavePlugin)
         .add_plugin(z::ZPlugin)
         .add_startup_system(setup.system())
         .run();
@@ -1,17 +1,551 @@
+mod file;
+mod generate;
+mod interiors;
 mod objects;
+mod placement;
+mod stream;
 mod tiles;
 
+use std::collections::HashMap;
+
+use bevy::asset::Asset;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy_asset_ron::RonAssetPlugin;
+use file::MapFile;
+pub use interiors::{teleport, Interiors, Location};
+use objects::ObjectCatalogue;
+use stream::StreamedChunks;
+
+use crate::params;
+use crate::player::Player;
+use crate::preload::Preload;
+use crate::state::AppState;
+use bevy_rapier2d::prelude::*;
 
 const SPRITE_SIZE: f32 = 32.0;
-pub const MAP_WIDTH: f32 = 32.0 * SPRITE_SIZE;
-pub const MAP_HEIGHT: f32 = 32.0 * SPRITE_SIZE;
+/// Size of random maps, and of each chunk of an endless world
+const DEFAULT_MAP_SIZE: f32 = 32.0 * SPRITE_SIZE;
+/// Thickness of the walls around the edges of a map
+const BOUNDARY_THICKNESS: f32 = SPRITE_SIZE;
+
+const DEFAULT_MAP: &str = "map/village.map.ron";
+
+/// Name the map preloads the assets its data refers to under
+const PRELOAD_NAME: &str = "map";
+
+/// Where the world is built from. Insert before adding `MapPlugin` to override the default map.
+#[derive(Clone)]
+pub enum MapSource {
+    /// Path to a `.map.ron` asset
+    File(String),
+    /// Randomly place tiles and objects from the catalogue, using `MapSeed`
+    Random,
+    /// A world without edges, randomly generated in chunks the size of a map as the player
+    /// approaches them
+    Endless,
+}
+
+impl Default for MapSource {
+    fn default() -> Self {
+        MapSource::File(DEFAULT_MAP.to_string())
+    }
+}
+
+/// Seed for random maps. Defaults to a random seed, or the `seed` launch parameter.
+pub struct MapSeed(pub u64);
+
+impl Default for MapSeed {
+    fn default() -> Self {
+        MapSeed(rand::random())
+    }
+}
+
+/// The outdoor map, from the bottom left corner at the origin to its top right. Taken from the
+/// loaded map, and the size random maps are generated at.
+#[derive(Clone, Copy)]
+pub struct MapBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl MapBounds {
+    fn from_size(size: Vec2) -> Self {
+        MapBounds {
+            min: Vec2::ZERO,
+            max: size,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn centre(&self) -> Vec2 {
+        (self.min + self.max) / 2.0
+    }
+}
+
+impl Default for MapBounds {
+    fn default() -> Self {
+        MapBounds::from_size(Vec2::splat(DEFAULT_MAP_SIZE))
+    }
+}
+
+/// Marks every entity built from the map so it can be replaced on reload.
+pub struct MapEntity;
+
+/// The part of the world the player is in, which the camera stays within.
+#[derive(Clone, Copy)]
+pub enum ActiveArea {
+    Bounded {
+        min: Vec2,
+        max: Vec2,
+    },
+    /// Endless worlds have no edges to stay within
+    Unbounded,
+}
+
+impl ActiveArea {
+    /// The whole map, or no bounds at all in an endless world.
+    pub fn outdoors(map_source: &MapSource, bounds: &MapBounds) -> Self {
+        match map_source {
+            MapSource::Endless => ActiveArea::Unbounded,
+            _ => ActiveArea::Bounded {
+                min: bounds.min,
+                max: bounds.max,
+            },
+        }
+    }
+}
+
+/// The stable ID of a placed object, from the map data.
+pub struct ObjectId(pub String);
+
+/// Looks up placed objects and their interactables by ID. Interactable IDs are the object's ID
+/// followed by the interactable's name, like `house-1/door`.
+#[derive(Default)]
+pub struct MapIds(HashMap<String, Entity>);
+
+impl MapIds {
+    pub fn get(&self, id: &str) -> Option<Entity> {
+        self.0.get(id).copied()
+    }
+
+    /// Forgets the IDs of despawned entities.
+    fn remove(&mut self, entities: &[Entity]) {
+        self.0.retain(|_, entity| !entities.contains(entity));
+    }
+
+    fn insert(&mut self, id: &str, entity: Entity) {
+        if self.0.insert(id.to_string(), entity).is_some() {
+            warn!("Duplicate map ID: {}", id);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Assets the map is built from.
+#[derive(SystemParam)]
+pub struct MapAssets<'a> {
+    asset_server: Res<'a, AssetServer>,
+    materials: ResMut<'a, Assets<ColorMaterial>>,
+    meshes: ResMut<'a, Assets<Mesh>>,
+    texture_atlases: ResMut<'a, Assets<TextureAtlas>>,
+}
+
+/// Sent to replace the world with one built from another source.
+pub struct LoadMap {
+    pub source: MapSource,
+    pub seed: u64,
+}
+
+/// Sent once the world has been built or rebuilt.
+pub struct MapBuilt;
+
+/// Whether the world needs to be built again, once its data has loaded.
+struct Rebuild {
+    pending: bool,
+    /// Also moves the player to the map's spawn point
+    respawn: bool,
+}
+
+impl Default for Rebuild {
+    fn default() -> Self {
+        // The map data finishes loading while preloading, before changes are watched for, so build
+        // the world as soon as the game starts
+        Rebuild {
+            pending: true,
+            respawn: true,
+        }
+    }
+}
+
+pub struct MapHandles {
+    catalogue: Handle<ObjectCatalogue>,
+    map: Option<Handle<MapFile>>,
+}
+
+/// The loaded map data.
+#[derive(SystemParam)]
+pub struct MapData<'a> {
+    handles: Res<'a, MapHandles>,
+    catalogues: Res<'a, Assets<ObjectCatalogue>>,
+    maps: Res<'a, Assets<MapFile>>,
+}
+
+impl<'a> MapData<'a> {
+    fn catalogue(&self) -> Option<&ObjectCatalogue> {
+        self.catalogues.get(&self.handles.catalogue)
+    }
+}
 
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(objects::setup.system())
-            .add_startup_system(tiles::setup.system());
+        // Passing a seed generates a random map from it
+        if let Some(seed) = params::get("seed").and_then(|seed| seed.parse().ok()) {
+            app.insert_resource(MapSource::Random)
+                .insert_resource(MapSeed(seed));
+        }
+        if params::get("world").as_deref() == Some("endless") {
+            app.insert_resource(MapSource::Endless);
+        }
+
+        app.init_resource::<MapSource>()
+            .init_resource::<MapSeed>()
+            .init_resource::<MapBounds>()
+            .init_resource::<Location>()
+            .init_resource::<Interiors>()
+            .init_resource::<MapIds>()
+            .init_resource::<StreamedChunks>()
+            .init_resource::<Rebuild>()
+            .add_event::<LoadMap>()
+            .add_event::<MapBuilt>()
+            .insert_resource(ClearColor(Color::BLACK))
+            .add_plugin(RonAssetPlugin::<ObjectCatalogue>::new(&[
+                objects::CATALOGUE_EXTENSION,
+            ]))
+            .add_plugin(RonAssetPlugin::<MapFile>::new(&[file::MAP_EXTENSION]))
+            .add_startup_system(load_data.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Loading).with_system(preload_assets.system()),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(setup.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(watch_changes.system())
+                    .with_system(load_map.system())
+                    .with_system(build.system())
+                    .with_system(stream::stream.system())
+                    .with_system(tiles::cull_chunks.system())
+                    .with_system(interiors::use_doors.system())
+                    .with_system(interiors::enter_interiors.system()),
+            );
+    }
+}
+
+fn load_data(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    map_source: Res<MapSource>,
+    mut preload: ResMut<Preload>,
+) {
+    preload.load(&asset_server, objects::CATALOGUE_PATH);
+    if let MapSource::File(path) = &*map_source {
+        preload.load(&asset_server, path);
+    }
+    preload.discovering(PRELOAD_NAME);
+
+    commands.insert_resource(MapHandles {
+        catalogue: asset_server.load(objects::CATALOGUE_PATH),
+        map: match &*map_source {
+            MapSource::File(path) => Some(asset_server.load(path.as_str())),
+            MapSource::Random | MapSource::Endless => None,
+        },
+    });
+}
+
+/// Preloads the textures and interiors the map data refers to, once it's loaded.
+fn preload_assets(
+    data: MapData,
+    map_source: Res<MapSource>,
+    asset_server: Res<AssetServer>,
+    mut preload: ResMut<Preload>,
+) {
+    let catalogue = match data.catalogue() {
+        Some(catalogue) => catalogue,
+        None => return,
+    };
+
+    let mut maps = Vec::new();
+    if let MapSource::File(path) = &*map_source {
+        maps.push(path.as_str());
+    } else {
+        preload.load(&asset_server, generate::TILESET);
+    }
+    for object in &catalogue.objects {
+        preload.load(&asset_server, &object.path);
+        maps.extend(
+            object
+                .interactables
+                .iter()
+                .filter_map(|interactable| interactable.interior.as_deref()),
+        );
+    }
+
+    let mut loaded = true;
+    for path in maps {
+        preload.load(&asset_server, path);
+        match data.maps.get(path) {
+            Some(map) => preload.load(&asset_server, &map.tileset.path),
+            None => loaded = false,
+        }
+    }
+
+    if loaded {
+        preload.discovered(PRELOAD_NAME);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    map_source: Res<MapSource>,
+    seed: Res<MapSeed>,
+    bounds: Res<MapBounds>,
+) {
+    match *map_source {
+        MapSource::Random => info!("Generating map with seed {}", seed.0),
+        MapSource::Endless => info!("Generating endless world with seed {}", seed.0),
+        MapSource::File(_) => {}
+    }
+    commands.insert_resource(ActiveArea::outdoors(&map_source, &bounds));
+
+    // Map data is hot reloaded so it can be edited while the game is running
+    asset_server.watch_for_changes().unwrap();
+}
+
+/// Rebuilds the world whenever its data changes.
+fn watch_changes(
+    mut catalogue_events: EventReader<AssetEvent<ObjectCatalogue>>,
+    mut map_events: EventReader<AssetEvent<MapFile>>,
+    handles: Res<MapHandles>,
+    mut rebuild: ResMut<Rebuild>,
+) {
+    let catalogue_changed = changed(&mut catalogue_events, &handles.catalogue);
+    let map_changed = handles
+        .map
+        .as_ref()
+        .is_some_and(|handle| changed(&mut map_events, handle));
+
+    rebuild.pending |= catalogue_changed || map_changed;
+}
+
+fn load_map(
+    mut load_map: EventReader<LoadMap>,
+    mut map_source: ResMut<MapSource>,
+    mut seed: ResMut<MapSeed>,
+    mut handles: ResMut<MapHandles>,
+    mut rebuild: ResMut<Rebuild>,
+    asset_server: Res<AssetServer>,
+) {
+    for LoadMap {
+        source,
+        seed: new_seed,
+    } in load_map.iter()
+    {
+        handles.map = match source {
+            MapSource::File(path) => Some(asset_server.load(path.as_str())),
+            MapSource::Random | MapSource::Endless => None,
+        };
+        *map_source = source.clone();
+        seed.0 = *new_seed;
+        *rebuild = Rebuild {
+            pending: true,
+            respawn: true,
+        };
+    }
+}
+
+/// Builds the world once the map data has loaded, and rebuilds it when needed.
+#[allow(clippy::too_many_arguments)]
+fn build(
+    mut commands: Commands,
+    mut rebuild: ResMut<Rebuild>,
+    data: MapData,
+    map_source: Res<MapSource>,
+    mut bounds: ResMut<MapBounds>,
+    seed: Res<MapSeed>,
+    mut ids: ResMut<MapIds>,
+    mut streamed: ResMut<StreamedChunks>,
+    mut location: ResMut<Location>,
+    mut interiors: ResMut<Interiors>,
+    mut active_area: ResMut<ActiveArea>,
+    mut assets: MapAssets,
+    spawned_query: Query<Entity, With<MapEntity>>,
+    mut player_query: Query<&mut RigidBodyPosition, With<Player>>,
+    mut map_built: EventWriter<MapBuilt>,
+) {
+    if !rebuild.pending {
+        return;
+    }
+
+    let catalogue = match data.catalogue() {
+        Some(catalogue) => catalogue,
+        None => return,
+    };
+
+    let generated;
+    let map = match (&*map_source, &data.handles.map) {
+        // Endless worlds are streamed in around the player instead
+        (MapSource::Endless, _) => None,
+        (_, Some(handle)) => match data.maps.get(handle) {
+            Some(map) => Some(map),
+            None => return,
+        },
+        (_, None) => {
+            generated = generate::generate(catalogue, seed.0, &bounds);
+            Some(&generated)
+        }
+    };
+
+    for entity in spawned_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if let Some(map) = map {
+        *bounds = MapBounds::from_size(map.size());
+    }
+    let spawn = map
+        .and_then(|map| map.spawn)
+        .unwrap_or_else(|| bounds.centre());
+
+    // Interiors are rebuilt too, so bring the player back outside. Otherwise they're only moved
+    // to the spawn point when the map is first built or replaced.
+    if *location != Location::Outdoors || rebuild.respawn {
+        for mut position in player_query.iter_mut() {
+            interiors::teleport(&mut position, spawn);
+        }
+        *location = Location::Outdoors;
+    }
+    *rebuild = Rebuild {
+        pending: false,
+        respawn: false,
+    };
+    *active_area = ActiveArea::outdoors(&map_source, &bounds);
+    interiors.clear();
+    ids.clear();
+    streamed.clear();
+
+    if let Some(map) = map {
+        spawn_boundary(&mut commands, bounds.min, bounds.max);
+        tiles::spawn_layers(&mut commands, map, Vec2::ZERO, &mut assets);
+        objects::spawn_placed(
+            &mut commands,
+            catalogue,
+            map,
+            Vec2::ZERO,
+            None,
+            &mut ids,
+            &mut assets,
+        );
+    }
+
+    map_built.send(MapBuilt);
+}
+
+/// Spawns walls just outside each edge of the area, so the player can't walk off it.
+fn spawn_boundary(commands: &mut Commands, min: Vec2, max: Vec2) {
+    let centre = (min + max) / 2.0;
+    let half_size = (max - min) / 2.0;
+    let half_thickness = BOUNDARY_THICKNESS / 2.0;
+
+    // Long enough to close off the corners
+    let horizontal = ColliderShape::cuboid(half_size.x + BOUNDARY_THICKNESS, half_thickness);
+    let vertical = ColliderShape::cuboid(half_thickness, half_size.y + BOUNDARY_THICKNESS);
+
+    for (position, shape) in [
+        (
+            Vec2::new(centre.x, min.y - half_thickness),
+            horizontal.clone(),
+        ),
+        (Vec2::new(centre.x, max.y + half_thickness), horizontal),
+        (
+            Vec2::new(min.x - half_thickness, centre.y),
+            vertical.clone(),
+        ),
+        (Vec2::new(max.x + half_thickness, centre.y), vertical),
+    ] {
+        objects::spawn_hitbox(commands, position, shape);
+    }
+}
+
+fn changed<T: Asset>(events: &mut EventReader<AssetEvent<T>>, handle: &Handle<T>) -> bool {
+    let mut changed = false;
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle: h } | AssetEvent::Modified { handle: h } => {
+                changed |= h == handle;
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::AssetPlugin;
+
+    #[test]
+    fn builds_the_world_when_the_game_starts() {
+        let mut app = App::build();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Texture>()
+            .add_asset::<ColorMaterial>()
+            .add_asset::<Mesh>()
+            .add_asset::<TextureAtlas>()
+            .add_asset::<ObjectCatalogue>()
+            .add_asset::<MapFile>()
+            .add_event::<MapBuilt>()
+            .insert_resource(MapSource::Random)
+            .insert_resource(MapSeed(1))
+            .init_resource::<MapBounds>()
+            .init_resource::<Location>()
+            .init_resource::<Interiors>()
+            .init_resource::<MapIds>()
+            .init_resource::<StreamedChunks>()
+            .init_resource::<Rebuild>()
+            .insert_resource(ActiveArea::Unbounded)
+            .add_system(build.system());
+
+        let catalogue: ObjectCatalogue =
+            ron::from_str(include_str!("../assets/map/village.objects.ron")).unwrap();
+        let catalogue = app
+            .world_mut()
+            .get_resource_mut::<Assets<ObjectCatalogue>>()
+            .unwrap()
+            .add(catalogue);
+        app.insert_resource(MapHandles {
+            catalogue,
+            map: None,
+        });
+
+        let mut app = app.app;
+        app.update();
+
+        let built = app
+            .world
+            .query_filtered::<Entity, With<MapEntity>>()
+            .iter(&app.world)
+            .count();
+        assert!(built > 0);
     }
 }
@@ -0,0 +1,82 @@
+use super::objects::Volume;
+use super::SPRITE_SIZE;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+
+pub const MAP_EXTENSION: &str = "map.ron";
+
+/// A hand authored map, loaded from a `.map.ron` asset.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "1ca90ddc-22cd-4ee5-8211-555924c0d566"]
+pub struct MapFile {
+    pub tileset: Tileset,
+    /// Tile types. Tile ID `n` refers to `tiles[n - 1]`, and `0` leaves a cell empty.
+    pub tiles: Vec<TileType>,
+    /// Layers of tile IDs, drawn in order. Each layer lists its rows from the top of the map down.
+    /// Whether a cell blocks movement is decided by its tile in the topmost layer.
+    pub layers: Vec<Vec<Vec<usize>>>,
+    #[serde(default)]
+    pub objects: Vec<PlacedObject>,
+    /// Walls and other hitboxes that aren't part of an object
+    #[serde(default)]
+    pub colliders: Vec<Volume>,
+    /// Where the player appears when the map is first built or the interior is entered. Defaults
+    /// to the centre of the map.
+    #[serde(default)]
+    pub spawn: Option<Vec2>,
+    /// The door leading back outside from an interior
+    #[serde(default)]
+    pub exit: Option<Volume>,
+}
+
+impl MapFile {
+    pub fn size(&self) -> Vec2 {
+        let rows = self.layers.iter().map(|rows| rows.len()).max().unwrap_or(0);
+        let columns = self
+            .layers
+            .iter()
+            .flatten()
+            .map(|ids| ids.len())
+            .max()
+            .unwrap_or(0);
+        Vec2::new(columns as f32, rows as f32) * SPRITE_SIZE
+    }
+}
+
+/// The sprite sheet tiles are drawn from, split into tiles of `SPRITE_SIZE`.
+#[derive(Debug, Deserialize)]
+pub struct Tileset {
+    pub path: String,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+/// A kind of terrain, like grass or water.
+#[derive(Debug, Deserialize)]
+pub struct TileType {
+    /// Index of the tile's sprite in the tileset
+    pub sprite: usize,
+    /// Picks from the 16 sprites starting at `sprite`, depending on which neighbouring cells
+    /// in the same layer have the same tile. Neighbours above, right, below and left add 1, 2, 4
+    /// and 8 to the index.
+    #[serde(default)]
+    pub autotile: bool,
+    /// Sprite whose quarters are drawn over an autotile's corners where the neighbours either side
+    /// of the corner match but the one diagonally across doesn't, so the terrain turns inwards
+    #[serde(default)]
+    pub inner_corners: Option<usize>,
+    /// Whether the player can't walk over the tile
+    #[serde(default)]
+    pub blocking: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlacedObject {
+    /// Unique within the map, and kept when the map is edited
+    pub id: String,
+    /// Name of the object type in the catalogue
+    pub object: String,
+    /// Bottom left of the object
+    pub position: Vec2,
+}
@@ -0,0 +1,231 @@
+use super::file::{MapFile, PlacedObject, TileType, Tileset};
+use super::objects::ObjectCatalogue;
+use super::placement::{Footprint, Placer};
+use super::{tiles, MapBounds, SPRITE_SIZE};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+pub const TILESET: &str = "textures/tiles/tileset.png";
+const TILESET_COLUMNS: usize = 16;
+const TILESET_ROWS: usize = 5;
+
+const GRASS: usize = 1;
+const TUFT: usize = 2;
+const WATER: usize = 3;
+const PATH: usize = 4;
+const SAND: usize = 5;
+const CLIFF: usize = 6;
+
+/// Half the width of the area kept clear around the player's spawn point
+const SPAWN_CLEARANCE: f32 = 48.0;
+
+const PONDS: usize = 3;
+/// Pond radius range, in tiles
+const POND_RADIUS: (f32, f32) = (1.5, 3.5);
+const CLIFFS: usize = 2;
+/// Cliff length range, in tiles
+const CLIFF_LENGTH: (usize, usize) = (4, 9);
+/// Paths are two tiles wide, so they run through the middle of the map
+const PATH_HALF_WIDTH: f32 = 1.0;
+/// Keeps terrain this many tiles away from paths, which pass through the spawn point
+const TERRAIN_CLEARANCE: f32 = 2.0;
+
+fn tile_types() -> Vec<TileType> {
+    // Sprite, autotile, inner corners, blocking
+    [
+        (0, false, None, false),
+        (1, false, None, false),
+        (16, true, Some(5), true),
+        (32, true, Some(6), false),
+        (48, true, Some(7), false),
+        (64, true, Some(8), true),
+    ]
+    .iter()
+    .map(|(sprite, autotile, inner_corners, blocking)| TileType {
+        sprite: *sprite,
+        autotile: *autotile,
+        inner_corners: *inner_corners,
+        blocking: *blocking,
+    })
+    .collect()
+}
+
+/// Generates a random map the size of `bounds`. The same seed, catalogue and size always generate
+/// the same map.
+pub fn generate(catalogue: &ObjectCatalogue, seed: u64, bounds: &MapBounds) -> MapFile {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let size = bounds.size();
+    let width = (size.x / SPRITE_SIZE) as usize;
+    let height = (size.y / SPRITE_SIZE) as usize;
+
+    let grass = vec![vec![GRASS; width]; height];
+
+    // Paths lead from the spawn point to each edge of the map
+    let centre = Vec2::new(width as f32, height as f32) / 2.0;
+    let on_path = |x: f32, y: f32, margin: f32| {
+        (x - centre.x).abs() < PATH_HALF_WIDTH + margin
+            || (y - centre.y).abs() < PATH_HALF_WIDTH + margin
+    };
+    let mut terrain: Vec<Vec<usize>> = (0..height)
+        .map(|row| {
+            (0..width)
+                .map(|column| {
+                    if on_path(column as f32 + 0.5, row as f32 + 0.5, 0.0) {
+                        PATH
+                    } else {
+                        0
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let clear_of_paths = |x: f32, y: f32| !on_path(x, y, TERRAIN_CLEARANCE);
+
+    // Ponds are ringed by sand, and kept off the edges so they aren't cut off when maps are
+    // streamed side by side. Small maps leave out ponds that don't fit.
+    for _ in 0..PONDS {
+        let radius = rng.gen_range(POND_RADIUS.0..POND_RADIUS.1);
+        if (width.min(height) as f32) <= (radius + 1.0) * 2.0 {
+            continue;
+        }
+        let x = rng.gen_range(radius + 1.0..width as f32 - radius - 1.0);
+        let y = rng.gen_range(radius + 1.0..height as f32 - radius - 1.0);
+        for (row, tiles) in terrain.iter_mut().enumerate() {
+            for (column, tile) in tiles.iter_mut().enumerate() {
+                let (cell_x, cell_y) = (column as f32 + 0.5, row as f32 + 0.5);
+                let distance = Vec2::new(cell_x - x, cell_y - y).length();
+                if distance > radius + 1.0 || !clear_of_paths(cell_x, cell_y) {
+                    continue;
+                }
+                if distance <= radius {
+                    *tile = WATER;
+                } else if *tile == 0 {
+                    *tile = SAND;
+                }
+            }
+        }
+    }
+
+    for _ in 0..CLIFFS {
+        let length = rng.gen_range(CLIFF_LENGTH.0..CLIFF_LENGTH.1);
+        // Cliffs are kept a tile off the edges too
+        if height < 3 || width < length + 3 {
+            continue;
+        }
+        let row = rng.gen_range(1..height - 1);
+        let start = rng.gen_range(1..width - length - 1);
+        for (column, tile) in terrain[row].iter_mut().enumerate().skip(start).take(length) {
+            if clear_of_paths(column as f32 + 0.5, row as f32 + 0.5) {
+                *tile = CLIFF;
+            }
+        }
+    }
+
+    // Tufts only grow on bare grass
+    let tufts = terrain
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|tile| {
+                    if *tile == 0 && rng.gen_range(0..5) == 0 {
+                        TUFT
+                    } else {
+                        0
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut map = MapFile {
+        tileset: Tileset {
+            path: TILESET.to_string(),
+            columns: TILESET_COLUMNS,
+            rows: TILESET_ROWS,
+        },
+        tiles: tile_types(),
+        layers: vec![grass, terrain, tufts],
+        objects: Vec::new(),
+        colliders: Vec::new(),
+        spawn: None,
+        exit: None,
+    };
+
+    // Keep objects off the paths and blocking terrain
+    let spawn_point = size / 2.0;
+    let path_half_width = PATH_HALF_WIDTH * SPRITE_SIZE;
+    let mut reserved = vec![
+        Footprint::from_centre(spawn_point, Vec2::splat(SPAWN_CLEARANCE)),
+        Footprint::from_centre(spawn_point, Vec2::new(size.x, path_half_width)),
+        Footprint::from_centre(spawn_point, Vec2::new(path_half_width, size.y)),
+    ];
+    reserved.extend(tiles::blocking_areas(&map));
+    let mut placer = Placer::new(Footprint::new(Vec2::ZERO, size), &reserved);
+
+    for object in catalogue.objects.iter() {
+        for n in 0..object.count {
+            let position = (0..object.placement.attempts)
+                .map(|_| {
+                    Vec2::new(
+                        rng.gen_range(0..(size.x as u32).max(1)) as f32,
+                        rng.gen_range(0..(size.y as u32).max(1)) as f32,
+                    )
+                })
+                .find(|position| placer.try_place(object, *position));
+
+            match position {
+                Some(position) => map.objects.push(PlacedObject {
+                    id: format!("{}-{}", object.name, n + 1),
+                    object: object.name.clone(),
+                    position,
+                }),
+                None => warn!("No room to place {}", object.name),
+            }
+        }
+    }
+
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalogue() -> ObjectCatalogue {
+        ron::from_str(include_str!("../../assets/map/village.objects.ron")).unwrap()
+    }
+
+    fn bounds(columns: f32, rows: f32) -> MapBounds {
+        MapBounds::from_size(Vec2::new(columns, rows) * SPRITE_SIZE)
+    }
+
+    #[test]
+    fn generates_tiny_maps() {
+        let catalogue = catalogue();
+        for (columns, rows) in [(0.0, 0.0), (1.0, 1.0), (3.0, 2.0), (5.0, 12.0), (12.0, 5.0)] {
+            for seed in 0..20 {
+                let map = generate(&catalogue, seed, &bounds(columns, rows));
+                assert_eq!(map.size(), Vec2::new(columns, rows) * SPRITE_SIZE);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_map() {
+        let catalogue = catalogue();
+        let a = generate(&catalogue, 7, &bounds(32.0, 32.0));
+        let b = generate(&catalogue, 7, &bounds(32.0, 32.0));
+        let c = generate(&catalogue, 8, &bounds(32.0, 32.0));
+
+        let objects = |map: &MapFile| {
+            map.objects
+                .iter()
+                .map(|object| (object.id.clone(), object.position))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(a.layers, b.layers);
+        assert_eq!(objects(&a), objects(&b));
+        assert_ne!(a.layers, c.layers);
+    }
+}
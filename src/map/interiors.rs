@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use super::file::MapFile;
+use super::objects;
+use super::{tiles, ActiveArea, MapAssets, MapBounds, MapData, MapEntity, MapIds, MapSource};
+use crate::door::UseDoor;
+use crate::interactable::{Interactable, InteractableKind};
+use crate::player::Player;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+/// Interiors are built this far apart, so they never overlap.
+const INTERIOR_SPACING: f32 = 4096.0;
+/// Interiors are built this far below the outdoor map. Other maps are far smaller than this, and
+/// endless worlds leave out the chunks that would overlap interiors.
+const INTERIOR_DEPTH: f32 = 524288.0;
+
+/// A door that leads to an interior.
+pub struct Entrance {
+    pub interior: String,
+    /// Where the player returns to when leaving the interior
+    pub outside: Vec2,
+}
+
+/// Where the player is.
+#[derive(Clone, Default, PartialEq)]
+pub enum Location {
+    #[default]
+    Outdoors,
+    /// Waiting for the interior behind the door to load
+    Entering(String),
+    Inside(String),
+}
+
+/// Each door's interior. Interiors are built the first time they're entered, and kept until the
+/// map is rebuilt.
+#[derive(Default)]
+pub struct Interiors(HashMap<String, Interior>);
+
+impl Interiors {
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Bottom left of the interior behind the door, once the player has been through it.
+    pub fn origin(&self, door_id: &str) -> Option<Vec2> {
+        self.0.get(door_id).map(|interior| interior.origin)
+    }
+
+    /// Where the player comes out of the interior behind the door.
+    pub fn outside(&self, door_id: &str) -> Option<Vec2> {
+        self.0.get(door_id).map(|interior| interior.outside)
+    }
+}
+
+struct Interior {
+    map: Handle<MapFile>,
+    origin: Vec2,
+    outside: Vec2,
+    built: bool,
+}
+
+/// The ID of the way back out of the interior behind a door.
+fn exit_id(door_id: &str) -> String {
+    format!("{}/exit", door_id)
+}
+
+/// Whether an area overlaps the band interiors are built in.
+pub fn overlaps_interiors(min: Vec2, max: Vec2) -> bool {
+    min.y < -INTERIOR_DEPTH + INTERIOR_SPACING && max.y > -INTERIOR_DEPTH
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn use_doors(
+    mut use_door: EventReader<UseDoor>,
+    mut location: ResMut<Location>,
+    mut interiors: ResMut<Interiors>,
+    mut active_area: ResMut<ActiveArea>,
+    map_source: Res<MapSource>,
+    bounds: Res<MapBounds>,
+    asset_server: Res<AssetServer>,
+    entrance_query: Query<(&Interactable, &Entrance)>,
+    mut player_query: Query<&mut RigidBodyPosition, With<Player>>,
+) {
+    for UseDoor(door_id) in use_door.iter() {
+        match &*location {
+            Location::Outdoors => {
+                if let Some((_, entrance)) = entrance_query
+                    .iter()
+                    .find(|(interactable, _)| interactable.id == *door_id)
+                {
+                    let count = interiors.0.len();
+                    interiors
+                        .0
+                        .entry(door_id.clone())
+                        .or_insert_with(|| Interior {
+                            map: asset_server.load(entrance.interior.as_str()),
+                            origin: Vec2::new(
+                                -INTERIOR_SPACING * (count + 1) as f32,
+                                -INTERIOR_DEPTH,
+                            ),
+                            outside: entrance.outside,
+                            built: false,
+                        });
+                    *location = Location::Entering(door_id.clone());
+                }
+            }
+            Location::Inside(interior_id) if exit_id(interior_id) == *door_id => {
+                if let Some(interior) = interiors.0.get(interior_id) {
+                    for mut position in player_query.iter_mut() {
+                        teleport(&mut position, interior.outside);
+                    }
+                }
+                *active_area = ActiveArea::outdoors(&map_source, &bounds);
+                *location = Location::Outdoors;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Builds interiors as they're entered, then moves the player inside.
+#[allow(clippy::too_many_arguments)]
+pub fn enter_interiors(
+    mut commands: Commands,
+    mut location: ResMut<Location>,
+    mut interiors: ResMut<Interiors>,
+    mut active_area: ResMut<ActiveArea>,
+    mut ids: ResMut<MapIds>,
+    data: MapData,
+    mut assets: MapAssets,
+    mut player_query: Query<&mut RigidBodyPosition, With<Player>>,
+) {
+    let door_id = match &*location {
+        Location::Entering(door_id) => door_id.clone(),
+        _ => return,
+    };
+
+    let interior = match interiors.0.get_mut(&door_id) {
+        Some(interior) => interior,
+        None => return,
+    };
+
+    let (map, catalogue) = match (data.maps.get(&interior.map), data.catalogue()) {
+        (Some(map), Some(catalogue)) => (map, catalogue),
+        _ => return,
+    };
+
+    let size = map.size();
+    if !interior.built {
+        super::spawn_boundary(&mut commands, interior.origin, interior.origin + size);
+        tiles::spawn_layers(&mut commands, map, interior.origin, &mut assets);
+        objects::spawn_placed(
+            &mut commands,
+            catalogue,
+            map,
+            interior.origin,
+            Some(&door_id),
+            &mut ids,
+            &mut assets,
+        );
+
+        if let Some(exit) = &map.exit {
+            let exit_id = exit_id(&door_id);
+            let entity = commands
+                .spawn_bundle(ColliderBundle {
+                    collider_type: ColliderType::Sensor,
+                    position: (interior.origin + exit.offset).into(),
+                    shape: exit.shape.collider_shape(),
+                    ..Default::default()
+                })
+                .insert(Interactable {
+                    id: exit_id.clone(),
+                    kind: InteractableKind::Door,
+                    prompt: Some("leave".to_string()),
+                })
+                .insert(MapEntity)
+                .id();
+            ids.insert(&exit_id, entity);
+        }
+
+        interior.built = true;
+    }
+
+    let spawn = interior.origin + map.spawn.unwrap_or(size / 2.0);
+    for mut position in player_query.iter_mut() {
+        teleport(&mut position, spawn);
+    }
+
+    *active_area = ActiveArea::Bounded {
+        min: interior.origin,
+        max: interior.origin + size,
+    };
+    *location = Location::Inside(door_id);
+}
+
+pub fn teleport(position: &mut RigidBodyPosition, to: Vec2) {
+    position.position = to.into();
+    position.next_position = position.position;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exits_have_their_own_ids() {
+        assert_eq!(exit_id("house-1/door"), "house-1/door/exit");
+    }
+
+    #[test]
+    fn endless_worlds_leave_room_for_interiors() {
+        let chunk = Vec2::splat(1024.0);
+        let overlaps = |y: f32| overlaps_interiors(Vec2::new(0.0, y), Vec2::new(0.0, y) + chunk);
+
+        assert!(!overlaps(0.0));
+        assert!(!overlaps(-INTERIOR_DEPTH - chunk.y));
+        assert!(overlaps(-INTERIOR_DEPTH - chunk.y / 2.0));
+        assert!(overlaps(-INTERIOR_DEPTH + INTERIOR_SPACING - chunk.y / 2.0));
+        assert!(!overlaps(-INTERIOR_DEPTH + INTERIOR_SPACING));
+    }
+}
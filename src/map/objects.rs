@@ -1,118 +1,268 @@
-use super::{MAP_HEIGHT, MAP_WIDTH};
-use crate::door::Door;
+use super::file::MapFile;
+use super::interiors::Entrance;
+use super::{MapAssets, MapEntity, MapIds, ObjectId};
+use crate::game_camera::CameraCue;
+use crate::interactable::{Interactable, InteractableKind};
 use crate::z::ZSync;
 use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
 use bevy_rapier2d::prelude::*;
-use rand::Rng;
-
-struct ObjectDetails<'a> {
-    count: usize,
-    path: &'a str,
-    size: Vec2,
-    offset: Vec2,
-    hitboxes: Vec<(Vec2, ColliderShape)>,
-    doors: Vec<(Vec2, ColliderShape)>,
+use serde::Deserialize;
+
+pub const CATALOGUE_EXTENSION: &str = "objects.ron";
+pub const CATALOGUE_PATH: &str = "map/village.objects.ron";
+
+/// Every type of object that can be placed on the map, loaded from a `.objects.ron` asset.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "62ffbda5-328c-482f-87ed-9ea264122dfa"]
+pub struct ObjectCatalogue {
+    pub objects: Vec<ObjectDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ObjectDetails {
+    pub name: String,
+    /// How many to place when generating a random map
+    #[serde(default)]
+    pub count: usize,
+    pub path: String,
+    pub size: Vec2,
+    #[serde(default)]
+    pub offset: Vec2,
+    #[serde(default)]
+    pub hitboxes: Vec<Volume>,
+    #[serde(default)]
+    pub interactables: Vec<InteractableDetails>,
+    #[serde(default)]
+    pub placement: Placement,
+}
+
+/// How random maps place the object.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Placement {
+    /// Minimum gap to other objects
+    pub spacing: f32,
+    /// Random positions to try before giving up on placing the object
+    pub attempts: usize,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Placement {
+            spacing: 0.0,
+            attempts: 100,
+        }
+    }
+}
+
+/// A collider relative to the bottom left of the object's sprite.
+#[derive(Debug, Deserialize)]
+pub struct Volume {
+    pub offset: Vec2,
+    pub shape: Shape,
+}
+
+/// A sensor the player can interact with, relative to the bottom left of the object's sprite.
+#[derive(Debug, Deserialize)]
+pub struct InteractableDetails {
+    /// Distinguishes the object's interactables. Defaults to the interactable's index.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub offset: Vec2,
+    pub shape: Shape,
+    pub kind: InteractableKind,
+    /// Shown as "Press <interact key> to <prompt>". Defaults to the usual action for the kind.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// For doors, the interior map the door leads to
+    #[serde(default)]
+    pub interior: Option<String>,
+    /// For triggers, how the camera reacts when a player walks in
+    #[serde(default)]
+    pub camera: Option<CameraCue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum Shape {
+    /// Half extents
+    Cuboid(f32, f32),
+    /// Radius
+    Ball(f32),
+}
+
+impl Shape {
+    pub fn collider_shape(&self) -> ColliderShape {
+        match *self {
+            Shape::Cuboid(hx, hy) => ColliderShape::cuboid(hx, hy),
+            Shape::Ball(radius) => ColliderShape::ball(radius),
+        }
+    }
+
+    pub fn half_extents(&self) -> Vec2 {
+        match *self {
+            Shape::Cuboid(hx, hy) => Vec2::new(hx, hy),
+            Shape::Ball(radius) => Vec2::splat(radius),
+        }
+    }
 }
 
-pub fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    let objects = vec![
-        ObjectDetails {
-            count: 10,
-            path: "textures/house.png",
-            size: Vec2::new(76.0, 55.0),
-            offset: Vec2::new(2.0, 0.0),
-            hitboxes: vec![(Vec2::new(38.0, 20.0), ColliderShape::cuboid(36.0, 20.0))],
-            doors: vec![(Vec2::new(48.0, 16.0), ColliderShape::cuboid(16.0, 16.0))],
-        },
-        ObjectDetails {
-            count: 7,
-            path: "textures/teepee.png",
-            size: Vec2::new(46.0, 61.0),
-            offset: Vec2::new(0.0, 0.0),
-            hitboxes: vec![(Vec2::new(23.0, 10.0), ColliderShape::cuboid(23.0, 10.0))],
-            doors: vec![],
-        },
-        ObjectDetails {
-            count: 1,
-            path: "textures/double_teepee.png",
-            size: Vec2::new(92.0, 61.0),
-            offset: Vec2::new(0.0, 0.0),
-            hitboxes: vec![(Vec2::new(46.0, 10.0), ColliderShape::cuboid(46.0, 10.0))],
-            doors: vec![],
-        },
-        ObjectDetails {
-            count: 20,
-            path: "textures/oak_tree.png",
-            size: Vec2::new(62.0, 111.0),
-            offset: Vec2::new(0.0, 0.0),
-            hitboxes: vec![(Vec2::new(31.0, 10.0), ColliderShape::ball(10.0))],
-            doors: vec![],
-        },
-    ];
-
-    let mut rng = rand::thread_rng();
-
-    let mut door_count = 0;
-
-    for object in objects.iter() {
-        let handle = materials.add(asset_server.load(object.path).into());
-        for _ in 0..object.count {
-            let x = rng.gen_range(0..MAP_WIDTH as u32) as f32;
-            let y = rng.gen_range(0..MAP_HEIGHT as u32) as f32;
-
-            commands
-                .spawn()
-                .insert(Transform {
-                    translation: Vec3::new(x, y, 0.0),
+/// Spawns the map's objects and free standing colliders, relative to `origin`. Object IDs are
+/// prefixed with `scope`, so maps built more than once (like interiors) don't share IDs. Returns
+/// the spawned entities.
+pub fn spawn_placed(
+    commands: &mut Commands,
+    catalogue: &ObjectCatalogue,
+    map: &MapFile,
+    origin: Vec2,
+    scope: Option<&str>,
+    ids: &mut MapIds,
+    assets: &mut MapAssets,
+) -> Vec<Entity> {
+    let handles = load_materials(catalogue, assets);
+    let mut entities = Vec::new();
+
+    for placed_object in map.objects.iter() {
+        match catalogue
+            .objects
+            .iter()
+            .position(|object| object.name == placed_object.object)
+        {
+            Some(index) => entities.extend(spawn_object(
+                commands,
+                &catalogue.objects[index],
+                handles[index].clone(),
+                origin + placed_object.position,
+                &match scope {
+                    Some(scope) => format!("{}/{}", scope, placed_object.id),
+                    None => placed_object.id.clone(),
+                },
+                ids,
+            )),
+            None => warn!("Unknown object type: {}", placed_object.object),
+        }
+    }
+
+    for collider in map.colliders.iter() {
+        entities.push(spawn_hitbox(
+            commands,
+            origin + collider.offset,
+            collider.shape.collider_shape(),
+        ));
+    }
+
+    entities
+}
+
+fn load_materials(
+    catalogue: &ObjectCatalogue,
+    assets: &mut MapAssets,
+) -> Vec<Handle<ColorMaterial>> {
+    catalogue
+        .objects
+        .iter()
+        .map(|object| {
+            let texture = assets.asset_server.load(object.path.as_str());
+            assets.materials.add(texture.into())
+        })
+        .collect()
+}
+
+fn spawn_object(
+    commands: &mut Commands,
+    object: &ObjectDetails,
+    material: Handle<ColorMaterial>,
+    position: Vec2,
+    id: &str,
+    ids: &mut MapIds,
+) -> Vec<Entity> {
+    let root = commands
+        .spawn()
+        .insert(Transform {
+            translation: position.extend(0.0),
+            ..Default::default()
+        })
+        .insert(GlobalTransform::default())
+        .insert(ObjectId(id.to_string()))
+        .insert(MapEntity)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(SpriteBundle {
+                    material,
+                    transform: Transform {
+                        translation: Vec3::new(
+                            object.size.x / 2.0 + object.offset.x,
+                            object.size.y / 2.0 + object.offset.y,
+                            0.0,
+                        ),
+                        ..Default::default()
+                    },
                     ..Default::default()
                 })
-                .insert(GlobalTransform::default())
-                .with_children(|parent| {
-                    parent
-                        .spawn_bundle(SpriteBundle {
-                            material: handle.clone(),
-                            transform: Transform {
-                                translation: Vec3::new(
-                                    object.size.x / 2.0 + object.offset.x,
-                                    object.size.y / 2.0 + object.offset.y,
-                                    0.0,
-                                ),
-                                ..Default::default()
-                            },
-                            ..Default::default()
-                        })
-                        .insert(ZSync::default());
-                });
-
-            for (offset, shape) in object.hitboxes.iter() {
-                commands
-                    .spawn_bundle(RigidBodyBundle {
-                        position: (object.offset + *offset + Vec2::new(x, y)).into(),
-                        body_type: RigidBodyType::Static,
-                        ..Default::default()
-                    })
-                    .insert_bundle(ColliderBundle {
-                        shape: shape.clone(),
-                        ..Default::default()
-                    })
-                    .insert(ColliderPositionSync::Discrete);
-            }
-
-            for (offset, shape) in object.doors.iter() {
-                commands
-                    .spawn_bundle(ColliderBundle {
-                        position: (object.offset + *offset + Vec2::new(x, y)).into(),
-                        shape: shape.clone(),
-                        ..Default::default()
-                    })
-                    .insert(Door(door_count));
+                .insert(ZSync::default());
+        })
+        .id();
+    ids.insert(id, root);
+    let mut entities = vec![root];
+
+    for hitbox in object.hitboxes.iter() {
+        entities.push(spawn_hitbox(
+            commands,
+            object.offset + hitbox.offset + position,
+            hitbox.shape.collider_shape(),
+        ));
+    }
+
+    for (index, details) in object.interactables.iter().enumerate() {
+        let interactable_id = match &details.name {
+            Some(name) => format!("{}/{}", id, name),
+            None => format!("{}/{}", id, index),
+        };
+        let centre = object.offset + details.offset + position;
+        let mut entity = commands.spawn_bundle(ColliderBundle {
+            collider_type: ColliderType::Sensor,
+            position: centre.into(),
+            shape: details.shape.collider_shape(),
+            ..Default::default()
+        });
+        entity
+            .insert(Interactable {
+                id: interactable_id.clone(),
+                kind: details.kind,
+                prompt: details.prompt.clone(),
+            })
+            .insert(MapEntity);
 
-                door_count += 1;
-            }
+        if let Some(interior) = &details.interior {
+            entity.insert(Entrance {
+                interior: interior.clone(),
+                // Just in front of the door
+                outside: centre - Vec2::new(0.0, details.shape.half_extents().y * 2.0),
+            });
         }
+        if let Some(cue) = details.camera {
+            entity.insert(cue.placed_at(object.offset + position));
+        }
+
+        ids.insert(&interactable_id, entity.id());
+        entities.push(entity.id());
     }
+
+    entities
+}
+
+pub fn spawn_hitbox(commands: &mut Commands, position: Vec2, shape: ColliderShape) -> Entity {
+    commands
+        .spawn_bundle(RigidBodyBundle {
+            position: position.into(),
+            body_type: RigidBodyType::Static,
+            ..Default::default()
+        })
+        .insert_bundle(ColliderBundle {
+            shape,
+            ..Default::default()
+        })
+        .insert(ColliderPositionSync::Discrete)
+        .insert(MapEntity)
+        .id()
 }
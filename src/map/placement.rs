@@ -0,0 +1,193 @@
+use super::objects::ObjectDetails;
+use crate::interactable::InteractableKind;
+use bevy::prelude::*;
+
+/// An axis aligned rectangle an object occupies.
+#[derive(Clone, Copy, Debug)]
+pub struct Footprint {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Footprint {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Footprint { min, max }
+    }
+
+    pub fn from_centre(centre: Vec2, half_extents: Vec2) -> Self {
+        Footprint::new(centre - half_extents, centre + half_extents)
+    }
+
+    pub fn centre(&self) -> Vec2 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn half_extents(&self) -> Vec2 {
+        (self.max - self.min) / 2.0
+    }
+
+    fn translate(&self, offset: Vec2) -> Self {
+        Footprint::new(self.min + offset, self.max + offset)
+    }
+
+    fn expand(&self, amount: f32) -> Self {
+        Footprint::new(
+            self.min - Vec2::splat(amount),
+            self.max + Vec2::splat(amount),
+        )
+    }
+
+    pub fn overlaps(&self, other: &Footprint) -> bool {
+        self.min.x < other.max.x
+            && other.min.x < self.max.x
+            && self.min.y < other.max.y
+            && other.min.y < self.max.y
+    }
+
+    fn contains(&self, other: &Footprint) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && other.max.x <= self.max.x
+            && other.max.y <= self.max.y
+    }
+}
+
+/// Footprints of an object placed at the origin: its sprite, and the space in front of each
+/// door so doors can always be reached.
+fn footprints(object: &ObjectDetails) -> Vec<Footprint> {
+    let mut footprints = vec![Footprint::new(object.offset, object.offset + object.size)];
+
+    for door in object
+        .interactables
+        .iter()
+        .filter(|interactable| interactable.kind == InteractableKind::Door)
+    {
+        let half_extents = door.shape.half_extents();
+        let centre = object.offset + door.offset - Vec2::new(0.0, half_extents.y * 2.0);
+        footprints.push(Footprint::from_centre(centre, half_extents));
+    }
+
+    footprints
+}
+
+/// Tracks the space used by placed objects, so new objects don't overlap them.
+pub struct Placer {
+    bounds: Footprint,
+    taken: Vec<(Footprint, f32)>,
+}
+
+impl Placer {
+    /// `reserved` areas, like the player's spawn point, are kept clear of objects.
+    pub fn new(bounds: Footprint, reserved: &[Footprint]) -> Self {
+        Placer {
+            bounds,
+            taken: reserved.iter().map(|footprint| (*footprint, 0.0)).collect(),
+        }
+    }
+
+    /// Places the object at the position if it fits on the map, keeping the greater of the two
+    /// objects' spacing between it and every other object.
+    pub fn try_place(&mut self, object: &ObjectDetails, position: Vec2) -> bool {
+        let spacing = object.placement.spacing;
+        let placed: Vec<Footprint> = footprints(object)
+            .iter()
+            .map(|footprint| footprint.translate(position))
+            .collect();
+
+        let fits = placed.iter().all(|footprint| {
+            self.bounds.contains(footprint)
+                && self.taken.iter().all(|(taken, taken_spacing)| {
+                    !footprint
+                        .expand(spacing.max(*taken_spacing))
+                        .overlaps(taken)
+                })
+        });
+
+        if fits {
+            self.taken
+                .extend(placed.into_iter().map(|footprint| (footprint, spacing)));
+        }
+
+        fits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 16 pixel square object, with a 16 pixel door in the middle of its bottom edge if it's
+    /// twice the size.
+    fn object(size: f32, spacing: f32) -> ObjectDetails {
+        let door = "(offset: (16.0, 8.0), shape: Cuboid(8.0, 8.0), kind: Door)";
+        ron::from_str(&format!(
+            "(
+                name: \"test\",
+                path: \"test.png\",
+                size: ({0}, {0}),
+                interactables: [{1}],
+                placement: (spacing: {2}),
+            )",
+            size,
+            if size >= 32.0 { door } else { "" },
+            spacing,
+        ))
+        .unwrap()
+    }
+
+    fn placer(reserved: &[Footprint]) -> Placer {
+        Placer::new(Footprint::new(Vec2::ZERO, Vec2::splat(100.0)), reserved)
+    }
+
+    #[test]
+    fn objects_dont_overlap() {
+        let mut placer = placer(&[]);
+        let object = object(16.0, 0.0);
+
+        assert!(placer.try_place(&object, Vec2::new(10.0, 10.0)));
+        assert!(!placer.try_place(&object, Vec2::new(20.0, 20.0)));
+        assert!(placer.try_place(&object, Vec2::new(26.0, 10.0)));
+    }
+
+    #[test]
+    fn objects_keep_the_larger_spacing_between_them() {
+        let mut placer = placer(&[]);
+
+        assert!(placer.try_place(&object(16.0, 4.0), Vec2::new(10.0, 10.0)));
+        assert!(!placer.try_place(&object(16.0, 0.0), Vec2::new(28.0, 10.0)));
+        assert!(placer.try_place(&object(16.0, 0.0), Vec2::new(30.0, 10.0)));
+        assert!(!placer.try_place(&object(16.0, 8.0), Vec2::new(52.0, 10.0)));
+    }
+
+    #[test]
+    fn objects_stay_inside_the_map() {
+        let mut placer = placer(&[]);
+        let object = object(16.0, 0.0);
+
+        assert!(!placer.try_place(&object, Vec2::new(90.0, 10.0)));
+        assert!(!placer.try_place(&object, Vec2::new(-1.0, 10.0)));
+        assert!(placer.try_place(&object, Vec2::new(84.0, 84.0)));
+    }
+
+    #[test]
+    fn the_space_in_front_of_doors_stays_clear() {
+        let mut placer = placer(&[]);
+
+        // The door's clear space would be off the bottom of the map
+        assert!(!placer.try_place(&object(32.0, 0.0), Vec2::new(40.0, 10.0)));
+        // Leaves the space from (48, 24) to (64, 40) clear
+        assert!(placer.try_place(&object(32.0, 0.0), Vec2::new(40.0, 40.0)));
+        assert!(!placer.try_place(&object(16.0, 0.0), Vec2::new(50.0, 20.0)));
+        assert!(placer.try_place(&object(16.0, 0.0), Vec2::new(70.0, 20.0)));
+    }
+
+    #[test]
+    fn reserved_areas_stay_clear() {
+        let mut placer = placer(&[Footprint::new(Vec2::splat(40.0), Vec2::splat(60.0))]);
+        let object = object(16.0, 4.0);
+
+        assert!(!placer.try_place(&object, Vec2::new(45.0, 45.0)));
+        assert!(!placer.try_place(&object, Vec2::new(22.0, 42.0)));
+        assert!(placer.try_place(&object, Vec2::new(20.0, 20.0)));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use super::interiors::{self, Location};
+use super::objects;
+use super::{generate, tiles, MapAssets, MapBounds, MapData, MapIds, MapSeed, MapSource};
+use crate::player::Player;
+use bevy::prelude::*;
+
+/// Chunks within this many chunks of a player's chunk are loaded
+const LOAD_DISTANCE: i32 = 1;
+/// Chunks further away than this are unloaded. This is more than `LOAD_DISTANCE`, so walking back
+/// and forth over the edge of a chunk doesn't keep loading and unloading its neighbours.
+const UNLOAD_DISTANCE: i32 = 2;
+
+/// Entities spawned for each loaded chunk of an endless world, by chunk coordinates.
+#[derive(Default)]
+pub struct StreamedChunks(HashMap<(i32, i32), Vec<Entity>>);
+
+impl StreamedChunks {
+    /// Forgets the loaded chunks, for when their entities have already been despawned.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Generates the chunks of an endless world around the players, and despawns chunks they've all
+/// left behind. Each chunk is a random map, seeded from the world's seed and the chunk's coordinates.
+#[allow(clippy::too_many_arguments)]
+pub fn stream(
+    mut commands: Commands,
+    map_source: Res<MapSource>,
+    seed: Res<MapSeed>,
+    location: Res<Location>,
+    bounds: Res<MapBounds>,
+    data: MapData,
+    mut streamed: ResMut<StreamedChunks>,
+    mut ids: ResMut<MapIds>,
+    mut assets: MapAssets,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    // Interiors are built away from the world, so leave it as it is while the player's inside
+    if !matches!(*map_source, MapSource::Endless) || *location != Location::Outdoors {
+        return;
+    }
+
+    let catalogue = match data.catalogue() {
+        Some(catalogue) => catalogue,
+        None => return,
+    };
+
+    // Each chunk is the size of a map
+    let size = bounds.size();
+    let player_chunks: Vec<(i32, i32)> = player_query
+        .iter()
+        .map(|transform| {
+            (
+                (transform.translation.x / size.x).floor() as i32,
+                (transform.translation.y / size.y).floor() as i32,
+            )
+        })
+        .collect();
+    if player_chunks.is_empty() {
+        return;
+    }
+
+    let far_away: Vec<(i32, i32)> = streamed
+        .0
+        .keys()
+        .filter(|chunk| is_far_away(**chunk, &player_chunks))
+        .cloned()
+        .collect();
+    for chunk in far_away {
+        if let Some(entities) = streamed.0.remove(&chunk) {
+            for entity in entities.iter() {
+                commands.entity(*entity).despawn_recursive();
+            }
+            ids.remove(&entities);
+        }
+    }
+
+    for (x, y) in nearby(&player_chunks) {
+        let origin = Vec2::new(x as f32, y as f32) * size;
+        // Leave room for interiors, which are built far below the map
+        if streamed.0.contains_key(&(x, y)) || interiors::overlaps_interiors(origin, origin + size)
+        {
+            continue;
+        }
+
+        let map = generate::generate(catalogue, chunk_seed(seed.0, x, y), &bounds);
+        let mut entities = tiles::spawn_layers(&mut commands, &map, origin, &mut assets);
+        entities.extend(objects::spawn_placed(
+            &mut commands,
+            catalogue,
+            &map,
+            origin,
+            Some(&format!("{},{}", x, y)),
+            &mut ids,
+            &mut assets,
+        ));
+        streamed.0.insert((x, y), entities);
+    }
+}
+
+/// The chunks within `LOAD_DISTANCE` of any of the players' chunks.
+fn nearby(player_chunks: &[(i32, i32)]) -> impl Iterator<Item = (i32, i32)> + '_ {
+    player_chunks.iter().flat_map(|(chunk_x, chunk_y)| {
+        (chunk_y - LOAD_DISTANCE..=chunk_y + LOAD_DISTANCE).flat_map(move |y| {
+            (chunk_x - LOAD_DISTANCE..=chunk_x + LOAD_DISTANCE).map(move |x| (x, y))
+        })
+    })
+}
+
+/// Whether a chunk is further than `UNLOAD_DISTANCE` from every player's chunk.
+fn is_far_away((x, y): (i32, i32), player_chunks: &[(i32, i32)]) -> bool {
+    player_chunks
+        .iter()
+        .all(|(chunk_x, chunk_y)| (x - chunk_x).abs().max((y - chunk_y).abs()) > UNLOAD_DISTANCE)
+}
+
+/// Mixes the chunk's coordinates into the world's seed, so every chunk is different but always
+/// generates the same way. This is SplitMix64 stepped once per chunk, which never gives two chunks
+/// the same seed.
+fn chunk_seed(seed: u64, x: i32, y: i32) -> u64 {
+    let chunk = (x as u32 as u64) << 32 | y as u32 as u64;
+    let mut z = seed.wrapping_add(chunk.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_seeds_are_the_same_each_time_and_differ_between_chunks() {
+        for seed in [0, 1, 12345] {
+            assert_eq!(chunk_seed(seed, 3, -2), chunk_seed(seed, 3, -2));
+
+            let seeds: Vec<u64> = nearby(&[(0, 0)])
+                .map(|(x, y)| chunk_seed(seed, x, y))
+                .collect();
+            for (index, chunk_seed) in seeds.iter().enumerate() {
+                assert!(!seeds[index + 1..].contains(chunk_seed));
+            }
+        }
+    }
+
+    #[test]
+    fn loads_the_chunks_around_each_player() {
+        let chunks: Vec<(i32, i32)> = nearby(&[(0, 0), (5, 5)]).collect();
+
+        assert_eq!(chunks.len(), 18);
+        assert!(chunks.contains(&(-1, 1)));
+        assert!(chunks.contains(&(6, 4)));
+        assert!(!chunks.contains(&(2, 0)));
+    }
+
+    #[test]
+    fn unloads_chunks_only_once_every_player_is_well_past_them() {
+        let players = [(0, 0), (10, 0)];
+
+        assert!(!is_far_away((2, -2), &players));
+        assert!(!is_far_away((8, 1), &players));
+        assert!(is_far_away((3, 0), &players));
+        assert!(is_far_away((5, 0), &players));
+    }
+}
@@ -1,44 +1,408 @@
-use super::{MAP_HEIGHT, MAP_WIDTH, SPRITE_SIZE};
+use super::file::{MapFile, TileType};
+use super::objects;
+use super::placement::Footprint;
+use super::{MapAssets, MapEntity, SPRITE_SIZE};
+use crate::game_camera::GameCamera;
 use bevy::prelude::*;
-use rand::Rng;
+use bevy::render::mesh::Indices;
+use bevy::render::pipeline::PrimitiveTopology;
+use bevy::sprite::SpriteResizeMode;
+use bevy_rapier2d::prelude::*;
 
-const SPRITE_GRASS: &str = "textures/tiles/grass.png";
-const SPRITE_TUFT: &str = "textures/tiles/tuft.png";
+/// Width and height of a chunk, in tiles. Each layer of a chunk is drawn as a single mesh.
+const CHUNK_SIZE: usize = 16;
 
-pub fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    let mut rng = rand::thread_rng();
-
-    let grass_handle = materials.add(asset_server.load(SPRITE_GRASS).into());
-    let tuft_handle = materials.add(asset_server.load(SPRITE_TUFT).into());
-
-    for x in 0..(MAP_WIDTH / SPRITE_SIZE) as u32 {
-        for y in 0..(MAP_HEIGHT / SPRITE_SIZE) as u32 {
-            let transform = Transform {
-                translation: Vec3::new(
-                    (x as f32 + 0.5) * SPRITE_SIZE as f32,
-                    (y as f32 + 0.5) * SPRITE_SIZE as f32,
-                    0.0,
-                ),
-                ..Default::default()
+/// Part of a tile layer, drawn as one mesh.
+pub struct Chunk {
+    /// Size in pixels, from the chunk's bottom left
+    size: Vec2,
+}
+
+/// Spawns the map's tiles relative to `origin`, along with colliders for blocking tiles. Returns
+/// the spawned entities.
+pub fn spawn_layers(
+    commands: &mut Commands,
+    map: &MapFile,
+    origin: Vec2,
+    assets: &mut MapAssets,
+) -> Vec<Entity> {
+    let texture_atlas = TextureAtlas::from_grid(
+        assets.asset_server.load(map.tileset.path.as_str()),
+        Vec2::splat(SPRITE_SIZE),
+        map.tileset.columns,
+        map.tileset.rows,
+    );
+    let material = assets.materials.add(texture_atlas.texture.clone().into());
+
+    let mut entities = Vec::new();
+    let size = map.size() / SPRITE_SIZE;
+    let (columns, rows) = (size.x as usize, size.y as usize);
+    for (layer, layer_rows) in map.layers.iter().enumerate() {
+        for chunk_y in (0..rows).step_by(CHUNK_SIZE) {
+            for chunk_x in (0..columns).step_by(CHUNK_SIZE) {
+                let chunk = (chunk_x, chunk_y);
+                let mesh = match chunk_mesh(map, rows, layer_rows, &texture_atlas, chunk) {
+                    Some(mesh) => assets.meshes.add(mesh),
+                    None => continue,
+                };
+                let corner = Vec2::new(chunk_x as f32, chunk_y as f32) * SPRITE_SIZE;
+
+                let chunk = commands
+                    .spawn_bundle(SpriteBundle {
+                        mesh,
+                        material: material.clone(),
+                        // The mesh is already in pixels, so the sprite shader mustn't scale it
+                        sprite: Sprite {
+                            size: Vec2::ONE,
+                            resize_mode: SpriteResizeMode::Manual,
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(
+                            // Layers draw in order, and always beneath objects
+                            (origin + corner).extend(layer as f32),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(Chunk {
+                        size: Vec2::splat(CHUNK_SIZE as f32 * SPRITE_SIZE),
+                    })
+                    .insert(MapEntity)
+                    .id();
+                entities.push(chunk);
+            }
+        }
+    }
+    assets.texture_atlases.add(texture_atlas);
+
+    for area in blocking_areas(map) {
+        entities.push(objects::spawn_hitbox(
+            commands,
+            origin + area.centre(),
+            ColliderShape::cuboid(area.half_extents().x, area.half_extents().y),
+        ));
+    }
+
+    entities
+}
+
+/// Areas covered by blocking tiles, relative to the bottom left of the map. Neighbouring blocking
+/// tiles are merged into as few rectangles as possible, rather than one per cell.
+pub fn blocking_areas(map: &MapFile) -> Vec<Footprint> {
+    let size = map.size() / SPRITE_SIZE;
+    let (columns, rows) = (size.x as usize, size.y as usize);
+
+    // Later layers are drawn over earlier ones, so the topmost tile in each cell decides
+    let mut blocking = vec![vec![false; columns]; rows];
+    for layer in map.layers.iter() {
+        for (row, ids) in layer.iter().enumerate() {
+            // Every layer lists its rows from the top of the map, however many it has
+            let y = match rows.checked_sub(1 + row) {
+                Some(y) => y,
+                None => break,
             };
+            for (x, id) in ids.iter().enumerate() {
+                if let Some(tile) = id.checked_sub(1).and_then(|index| map.tiles.get(index)) {
+                    blocking[y][x] = tile.blocking;
+                }
+            }
+        }
+    }
 
-            commands.spawn().insert_bundle(SpriteBundle {
-                material: grass_handle.clone(),
-                transform,
-                ..Default::default()
-            });
+    // Grow each rectangle as far right as possible, then as far up as the whole row allows
+    let mut covered = vec![vec![false; columns]; rows];
+    let free = |covered: &Vec<Vec<bool>>, x: usize, y: usize| blocking[y][x] && !covered[y][x];
+    let mut areas = Vec::new();
+    for y in 0..rows {
+        for x in 0..columns {
+            if !free(&covered, x, y) {
+                continue;
+            }
+
+            let mut width = 1;
+            while x + width < columns && free(&covered, x + width, y) {
+                width += 1;
+            }
+            let mut height = 1;
+            while y + height < rows && (x..x + width).all(|x| free(&covered, x, y + height)) {
+                height += 1;
+            }
 
-            if rng.gen_range(0..5) == 0 {
-                commands.spawn().insert_bundle(SpriteBundle {
-                    material: tuft_handle.clone(),
-                    transform,
-                    ..Default::default()
-                });
+            for row in covered.iter_mut().skip(y).take(height) {
+                for cell in row.iter_mut().skip(x).take(width) {
+                    *cell = true;
+                }
             }
+            areas.push(Footprint::new(
+                Vec2::new(x as f32, y as f32) * SPRITE_SIZE,
+                Vec2::new((x + width) as f32, (y + height) as f32) * SPRITE_SIZE,
+            ));
         }
     }
+
+    areas
+}
+
+/// Picks an autotile's sprite from which of its neighbours match it. Cells off the edge of the map
+/// count as matching, so terrain carries on past the edge.
+fn sprite_index(tile: &TileType, rows: &[Vec<usize>], x: usize, row: usize) -> usize {
+    if !tile.autotile {
+        return tile.sprite;
+    }
+
+    // Above, right, below and left
+    let mask: usize = [(0, -1), (1, 0), (0, 1), (-1, 0)]
+        .iter()
+        .enumerate()
+        .filter(|(_, &offset)| matches(rows, x, row, offset))
+        .map(|(bit, _)| 1 << bit)
+        .sum();
+    tile.sprite + mask
+}
+
+/// Which of an autotile's top left, top right, bottom right and bottom left corners turn inwards,
+/// where the neighbours either side of the corner match it but the one diagonally across doesn't.
+fn inner_corners(tile: &TileType, rows: &[Vec<usize>], x: usize, row: usize) -> [bool; 4] {
+    let mut corners = [false; 4];
+    if !tile.autotile || tile.inner_corners.is_none() {
+        return corners;
+    }
+
+    for (corner, &(dx, drow)) in [(-1, -1), (1, -1), (1, 1), (-1, 1)].iter().enumerate() {
+        corners[corner] = matches(rows, x, row, (dx, 0))
+            && matches(rows, x, row, (0, drow))
+            && !matches(rows, x, row, (dx, drow));
+    }
+    corners
+}
+
+/// Whether the cell offset from `x` and `row` has the same tile. Cells off the edge of the map
+/// count as matching.
+fn matches(rows: &[Vec<usize>], x: usize, row: usize, (dx, drow): (isize, isize)) -> bool {
+    let (other_x, other_row) = (x as isize + dx, row as isize + drow);
+    other_x < 0
+        || other_row < 0
+        || rows
+            .get(other_row as usize)
+            .and_then(|ids| ids.get(other_x as usize))
+            .is_none_or(|other| *other == rows[row][x])
+}
+
+/// Vertices of the quads making up a chunk's mesh.
+#[derive(Default)]
+struct Quads {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl Quads {
+    /// Adds a quad from `min` to `max` drawing the part of the texture from `uv_min` to `uv_max`.
+    fn push(&mut self, (min, max): (Vec2, Vec2), (uv_min, uv_max): (Vec2, Vec2)) {
+        let start = self.positions.len() as u32;
+        self.positions.extend_from_slice(&[
+            [min.x, min.y, 0.0],
+            [max.x, min.y, 0.0],
+            [max.x, max.y, 0.0],
+            [min.x, max.y, 0.0],
+        ]);
+        // Texture coordinates run from the top left
+        self.uvs.extend_from_slice(&[
+            [uv_min.x, uv_max.y],
+            [uv_max.x, uv_max.y],
+            [uv_max.x, uv_min.y],
+            [uv_min.x, uv_min.y],
+        ]);
+        self.indices
+            .extend_from_slice(&[start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+}
+
+/// Builds a mesh with a quad for each tile in the chunk, relative to the chunk's bottom left.
+/// `map_rows` is the height of the map in tiles, which the layer's rows are listed down from.
+/// Returns `None` if the chunk has no tiles in the layer.
+fn chunk_mesh(
+    map: &MapFile,
+    map_rows: usize,
+    rows: &[Vec<usize>],
+    texture_atlas: &TextureAtlas,
+    (chunk_x, chunk_y): (usize, usize),
+) -> Option<Mesh> {
+    let mut quads = Quads::default();
+
+    for y in chunk_y..chunk_y + CHUNK_SIZE {
+        // Rows are listed from the top of the map down, and layers can have fewer of them
+        let row = match map_rows.checked_sub(1 + y) {
+            Some(row) => row,
+            None => break,
+        };
+        let ids = match rows.get(row) {
+            Some(ids) => ids,
+            None => continue,
+        };
+        for (x, &id) in ids
+            .iter()
+            .enumerate()
+            .take(chunk_x + CHUNK_SIZE)
+            .skip(chunk_x)
+        {
+            if id == 0 {
+                continue;
+            }
+
+            let tile = match map.tiles.get(id - 1) {
+                Some(tile) => tile,
+                None => {
+                    warn!("Unknown tile ID {} at {}, {}", id, x, y);
+                    continue;
+                }
+            };
+            let rect = match texture_atlas.textures.get(sprite_index(tile, rows, x, row)) {
+                Some(rect) => rect,
+                None => {
+                    warn!("Tile ID {} at {}, {} is outside the tileset", id, x, y);
+                    continue;
+                }
+            };
+
+            let min = Vec2::new((x - chunk_x) as f32, (y - chunk_y) as f32) * SPRITE_SIZE;
+            let corners = inner_corners(tile, rows, x, row);
+            let inner_rect = tile
+                .inner_corners
+                .and_then(|sprite| texture_atlas.textures.get(sprite));
+            let inner_rect = match inner_rect {
+                Some(inner_rect) if corners.contains(&true) => inner_rect,
+                _ => {
+                    quads.push(
+                        (min, min + Vec2::splat(SPRITE_SIZE)),
+                        (rect.min / texture_atlas.size, rect.max / texture_atlas.size),
+                    );
+                    continue;
+                }
+            };
+
+            // Draw each quarter of the tile from its own sprite, or the inner corner sprite where
+            // the corner turns inwards. Quarters go top left, top right, bottom right, bottom left.
+            let half = SPRITE_SIZE / 2.0;
+            for (corner, &(right, top)) in [(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)]
+                .iter()
+                .enumerate()
+            {
+                let rect = if corners[corner] { inner_rect } else { rect };
+                let quarter_min = min + Vec2::new(right, top) * half;
+                let uv_min = rect.min + Vec2::new(right, 1.0 - top) * half;
+                quads.push(
+                    (quarter_min, quarter_min + Vec2::splat(half)),
+                    (
+                        uv_min / texture_atlas.size,
+                        (uv_min + Vec2::splat(half)) / texture_atlas.size,
+                    ),
+                );
+            }
+        }
+    }
+
+    if quads.positions.is_empty() {
+        return None;
+    }
+    let Quads {
+        positions,
+        uvs,
+        indices,
+    } = quads;
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 0.0, 1.0]; positions.len()],
+    );
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    Some(mesh)
+}
+
+/// Hides chunks outside the camera's view, so they aren't drawn.
+pub fn cull_chunks(
+    windows: Res<Windows>,
+    camera_query: Query<&Transform, With<GameCamera>>,
+    mut chunk_query: Query<(&Chunk, &Transform, &mut Visible)>,
+) {
+    let (window, camera) = match (windows.get_primary(), camera_query.iter().next()) {
+        (Some(window), Some(camera)) => (window, camera),
+        _ => return,
+    };
+
+    let half_view = Vec2::new(window.width(), window.height()) * camera.scale.truncate() / 2.0;
+    let view = Footprint::from_centre(camera.translation.truncate(), half_view);
+
+    for (chunk, transform, mut visible) in chunk_query.iter_mut() {
+        let corner = transform.translation.truncate();
+        let is_visible = view.overlaps(&Footprint::new(corner, corner + chunk.size));
+        if visible.is_visible != is_visible {
+            visible.is_visible = is_visible;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(layers: &str) -> MapFile {
+        ron::from_str(&format!(
+            "(
+                tileset: (path: \"tileset.png\", columns: 16, rows: 5),
+                tiles: [
+                    (sprite: 0),
+                    (sprite: 1, blocking: true),
+                    (sprite: 16, autotile: true, inner_corners: Some(5)),
+                ],
+                layers: {},
+            )",
+            layers
+        ))
+        .unwrap()
+    }
+
+    fn texture_atlas() -> TextureAtlas {
+        TextureAtlas::from_grid(Handle::default(), Vec2::splat(SPRITE_SIZE), 16, 5)
+    }
+
+    #[test]
+    fn merges_neighbouring_blocking_tiles() {
+        let map = map("[[[2, 2, 1], [2, 2, 1], [1, 1, 1]]]");
+
+        let areas = blocking_areas(&map);
+        assert_eq!(areas.len(), 1);
+        assert_eq!(areas[0].centre(), Vec2::new(1.0, 2.0) * SPRITE_SIZE);
+        assert_eq!(areas[0].half_extents(), Vec2::splat(SPRITE_SIZE));
+    }
+
+    #[test]
+    fn shorter_layers_start_from_the_top_of_the_map() {
+        let map = map("[[[1, 1], [1, 1], [1, 1]], [[2, 2]]]");
+
+        let areas = blocking_areas(&map);
+        assert_eq!(areas.len(), 1);
+        assert_eq!(areas[0].centre(), Vec2::new(1.0, 2.5) * SPRITE_SIZE);
+    }
+
+    #[test]
+    fn empty_layers_have_no_tiles() {
+        let map = map("[[], [[]], [[1]]]");
+
+        assert!(blocking_areas(&map).is_empty());
+        assert!(chunk_mesh(&map, 1, &map.layers[0], &texture_atlas(), (0, 0)).is_none());
+        assert!(chunk_mesh(&map, 1, &map.layers[1], &texture_atlas(), (0, 0)).is_none());
+        assert!(chunk_mesh(&map, 1, &map.layers[2], &texture_atlas(), (0, 0)).is_some());
+    }
+
+    #[test]
+    fn inner_corners_are_drawn_in_quarters() {
+        let map = map("[[[1, 3, 3], [3, 3, 3]]]");
+
+        // Only the bottom middle tile has an inner corner, at its top left
+        let mesh = chunk_mesh(&map, 2, &map.layers[0], &texture_atlas(), (0, 0)).unwrap();
+        assert_eq!(mesh.count_vertices(), (5 + 4) * 4);
+    }
 }
@@ -1,14 +1,23 @@
+use crate::preload::Preload;
+use crate::state::AppState;
 use bevy::prelude::*;
 use bevy_kira_audio::Audio;
 
+const THEME: &str = "sound/theme.ogg";
+
 pub struct MusicPlugin;
 
 impl Plugin for MusicPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(setup.system());
+        app.add_startup_system(preload.system())
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(setup.system()));
     }
 }
 
+fn preload(asset_server: Res<AssetServer>, mut preload: ResMut<Preload>) {
+    preload.load(&asset_server, THEME);
+}
+
 fn setup(asset_server: Res<AssetServer>, audio: Res<Audio>) {
-    audio.play_looped(asset_server.load("sound/theme.ogg"));
+    audio.play_looped(asset_server.load(THEME));
 }
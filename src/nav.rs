@@ -0,0 +1,401 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::game_camera::{self, GameCamera};
+use crate::map::{ActiveArea, Location, MapBuilt};
+use crate::player::{self, Player, SecondPlayer};
+use crate::state::AppState;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+/// Size of each square of the navigation grid
+const CELL_SIZE: f32 = 8.0;
+/// How far the mouse can move between being pressed and released for it to count as a click
+/// rather than a drag
+const CLICK_DISTANCE: f32 = 8.0;
+/// How far around the player and where they clicked to look for a path in an endless world
+const SEARCH_MARGIN: f32 = 512.0;
+/// How close the player's feet get to each point on a path before heading to the next
+const ARRIVE_DISTANCE: f32 = 2.0;
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// Where the player is walking to after clicking, as points for their feet to pass through.
+#[derive(Default)]
+pub struct WalkPath(Vec<Vec2>);
+
+impl WalkPath {
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// The way to walk to follow the path from where the feet are, or zero at the end of it.
+    pub fn direction(&mut self, feet: Vec2) -> Vec2 {
+        while let Some(next) = self.0.first() {
+            if next.distance(feet) > ARRIVE_DISTANCE {
+                return (*next - feet).normalize();
+            }
+            self.0.remove(0);
+        }
+        Vec2::ZERO
+    }
+}
+
+/// Which parts of an area the player's feet can be in, in squares of `CELL_SIZE`.
+struct NavGrid {
+    min: Vec2,
+    columns: usize,
+    rows: usize,
+    blocked: Vec<bool>,
+}
+
+impl NavGrid {
+    /// Blocks the cells that would put the player's feet inside any of the obstacles.
+    fn new(min: Vec2, max: Vec2, obstacles: &[(Vec2, Vec2)]) -> Self {
+        let columns = ((max.x - min.x) / CELL_SIZE).ceil().max(1.0) as usize;
+        let rows = ((max.y - min.y) / CELL_SIZE).ceil().max(1.0) as usize;
+        let mut blocked = vec![false; columns * rows];
+
+        let feet = Vec2::splat(player::FEET_RADIUS);
+        for (obstacle_min, obstacle_max) in obstacles {
+            // Cells whose centres are inside the obstacle, grown by the size of the feet
+            let first = ((*obstacle_min - feet - min) / CELL_SIZE - Vec2::splat(0.5)).ceil();
+            let last = ((*obstacle_max + feet - min) / CELL_SIZE - Vec2::splat(0.5)).floor();
+            if last.x < 0.0 || last.y < 0.0 {
+                continue;
+            }
+            let last = last.min(Vec2::new(columns as f32 - 1.0, rows as f32 - 1.0));
+            let first = first.max(Vec2::ZERO);
+
+            for y in first.y as usize..=last.y as usize {
+                for x in first.x as usize..=last.x as usize {
+                    blocked[y * columns + x] = true;
+                }
+            }
+        }
+
+        NavGrid {
+            min,
+            columns,
+            rows,
+            blocked,
+        }
+    }
+
+    fn max(&self) -> Vec2 {
+        self.min + Vec2::new(self.columns as f32, self.rows as f32) * CELL_SIZE
+    }
+
+    fn covers(&self, min: Vec2, max: Vec2) -> bool {
+        self.min.cmple(min).all() && self.max().cmpge(max).all()
+    }
+
+    /// The cell a position is in, or the closest one to it.
+    fn cell(&self, position: Vec2) -> usize {
+        let cell = ((position - self.min) / CELL_SIZE).floor();
+        let x = (cell.x.max(0.0) as usize).min(self.columns - 1);
+        let y = (cell.y.max(0.0) as usize).min(self.rows - 1);
+        y * self.columns + x
+    }
+
+    fn centre(&self, cell: usize) -> Vec2 {
+        let x = (cell % self.columns) as f32 + 0.5;
+        let y = (cell / self.columns) as f32 + 0.5;
+        self.min + Vec2::new(x, y) * CELL_SIZE
+    }
+
+    /// The cell a step away in a direction, if it's inside the grid.
+    fn step(&self, cell: usize, dx: i32, dy: i32) -> Option<usize> {
+        let x = (cell % self.columns) as i32 + dx;
+        let y = (cell / self.columns) as i32 + dy;
+        if x < 0 || y < 0 || x >= self.columns as i32 || y >= self.rows as i32 {
+            return None;
+        }
+        Some(y as usize * self.columns + x as usize)
+    }
+
+    /// The closest open cell to a cell, searching outwards one ring at a time.
+    fn nearest_open(&self, cell: usize) -> Option<usize> {
+        let reach = self.columns.max(self.rows) as i32;
+        (0..reach).find_map(|ring| {
+            (-ring..=ring)
+                .flat_map(|dy| (-ring..=ring).map(move |dx| (dx, dy)))
+                .filter(|(dx, dy)| dx.abs() == ring || dy.abs() == ring)
+                .filter_map(|(dx, dy)| self.step(cell, dx, dy))
+                .filter(|cell| !self.blocked[*cell])
+                .min_by_key(|open| {
+                    let (ax, ay) = ((open % self.columns) as i32, (open / self.columns) as i32);
+                    let (bx, by) = ((cell % self.columns) as i32, (cell / self.columns) as i32);
+                    (ax - bx).pow(2) + (ay - by).pow(2)
+                })
+        })
+    }
+
+    /// Estimated cost between cells, moving diagonally as far as possible.
+    fn heuristic(&self, from: usize, to: usize) -> u32 {
+        let dx = ((from % self.columns) as i32 - (to % self.columns) as i32).unsigned_abs();
+        let dy = ((from / self.columns) as i32 - (to / self.columns) as i32).unsigned_abs();
+        DIAGONAL_COST * dx.min(dy) + STRAIGHT_COST * (dx.max(dy) - dx.min(dy))
+    }
+
+    /// Finds the shortest way between positions with A*, without cutting past the corners of
+    /// blocked cells. Heads for the closest open place if the destination is blocked.
+    fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let start = self.cell(from);
+        let target = self.cell(to);
+        let goal = self.nearest_open(target)?;
+
+        let mut costs = vec![u32::MAX; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        costs[start] = 0;
+        open.push(Reverse((self.heuristic(start, goal), start)));
+
+        while let Some(Reverse((estimate, current))) = open.pop() {
+            if current == goal {
+                break;
+            }
+            // Already reached more cheaply
+            if estimate > costs[current] + self.heuristic(current, goal) {
+                continue;
+            }
+
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let next = match self.step(current, dx, dy) {
+                        Some(next) if !self.blocked[next] => next,
+                        _ => continue,
+                    };
+                    let cost = if dx != 0 && dy != 0 {
+                        let open_sides = [self.step(current, dx, 0), self.step(current, 0, dy)]
+                            .iter()
+                            .all(|side| side.is_some_and(|side| !self.blocked[side]));
+                        if !open_sides {
+                            continue;
+                        }
+                        DIAGONAL_COST
+                    } else if dx != 0 || dy != 0 {
+                        STRAIGHT_COST
+                    } else {
+                        continue;
+                    };
+
+                    let cost = costs[current] + cost;
+                    if cost < costs[next] {
+                        costs[next] = cost;
+                        came_from[next] = current;
+                        open.push(Reverse((cost + self.heuristic(next, goal), next)));
+                    }
+                }
+            }
+        }
+
+        if costs[goal] == u32::MAX {
+            return None;
+        }
+
+        let mut cells = vec![goal];
+        while let Some(&cell) = cells.last() {
+            if cell == start {
+                break;
+            }
+            cells.push(came_from[cell]);
+        }
+
+        let mut points = vec![from];
+        points.extend(cells.iter().rev().skip(1).map(|cell| self.centre(*cell)));
+        // Walk right up to where was clicked, unless it's blocked
+        if goal == target {
+            if points.len() > 1 {
+                points.pop();
+            }
+            points.push(to);
+        }
+
+        Some(self.smooth(&points))
+    }
+
+    /// Cuts out points that can be walked past in a straight line. Leaves out the first point.
+    fn smooth(&self, points: &[Vec2]) -> Vec<Vec2> {
+        let mut smoothed = Vec::new();
+        let mut from = 0;
+        while from + 1 < points.len() {
+            let to = (from + 1..points.len())
+                .rev()
+                .find(|to| *to == from + 1 || self.in_sight(points[from], points[*to]))
+                .unwrap();
+            smoothed.push(points[to]);
+            from = to;
+        }
+        smoothed
+    }
+
+    fn in_sight(&self, from: Vec2, to: Vec2) -> bool {
+        let samples = (from.distance(to) / (CELL_SIZE / 4.0)).ceil() as usize;
+        (0..=samples).all(|sample| {
+            let position = from.lerp(to, sample as f32 / samples.max(1) as f32);
+            !self.blocked[self.cell(position)]
+        })
+    }
+}
+
+pub struct NavPlugin;
+
+impl Plugin for NavPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::Playing)
+                .with_system(click_to_move.system())
+                .with_system(stop_walking.system()),
+        );
+    }
+}
+
+/// Walks the player to where the mouse is clicked. The navigation grid is rebuilt from the static
+/// colliders for the next click after any are spawned or removed.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn click_to_move(
+    windows: Res<Windows>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    active_area: Res<ActiveArea>,
+    mut pressed_at: Local<Option<Vec2>>,
+    mut grid: Local<Option<NavGrid>>,
+    added_query: Query<(), Added<ColliderShape>>,
+    removed: RemovedComponents<ColliderShape>,
+    camera_query: Query<&Transform, With<GameCamera>>,
+    obstacle_query: Query<(&RigidBodyType, &RigidBodyPosition, &ColliderShape)>,
+    mut player_query: Query<(&Transform, &mut WalkPath), (With<Player>, Without<SecondPlayer>)>,
+) {
+    if added_query.iter().next().is_some() || removed.iter().next().is_some() {
+        *grid = None;
+    }
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if mouse_buttons.just_pressed(MouseButton::Left) {
+        *pressed_at = window.cursor_position();
+    }
+    if !mouse_buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    // Clicks sent for taps are dropped with the touch controls, so touch screens don't get here
+    let click = match (pressed_at.take(), window.cursor_position()) {
+        (Some(pressed), Some(released)) if pressed.distance(released) <= CLICK_DISTANCE => released,
+        _ => return,
+    };
+
+    let camera = match camera_query.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+    let target = game_camera::screen_to_world(window, camera, click);
+
+    for (transform, mut walk_path) in player_query.iter_mut() {
+        let feet = transform.translation.truncate() + Vec2::new(0.0, player::FEET_OFFSET);
+
+        let (min, max) = match *active_area {
+            ActiveArea::Bounded { min, max } => (min, max),
+            ActiveArea::Unbounded => (
+                feet.min(target) - Vec2::splat(SEARCH_MARGIN),
+                feet.max(target) + Vec2::splat(SEARCH_MARGIN),
+            ),
+        };
+        if !grid.as_ref().is_some_and(|grid| grid.covers(min, max)) {
+            let obstacles = obstacle_query
+                .iter()
+                .filter(|(body_type, ..)| **body_type == RigidBodyType::Static)
+                .map(|(_, position, shape)| {
+                    let aabb = shape.compute_aabb(&position.position);
+                    (
+                        Vec2::new(aabb.mins.x, aabb.mins.y),
+                        Vec2::new(aabb.maxs.x, aabb.maxs.y),
+                    )
+                })
+                .collect::<Vec<_>>();
+            *grid = Some(NavGrid::new(min, max, &obstacles));
+        }
+
+        if let Some(path) = grid.as_ref().and_then(|grid| grid.find_path(feet, target)) {
+            walk_path.0 = path;
+        }
+    }
+}
+
+/// Stops walking to a click when the player is moved somewhere else.
+fn stop_walking(
+    location: Res<Location>,
+    mut map_built: EventReader<MapBuilt>,
+    mut walk_path_query: Query<&mut WalkPath>,
+) {
+    if location.is_changed() || map_built.iter().next().is_some() {
+        for mut walk_path in walk_path_query.iter_mut() {
+            walk_path.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 96 pixel square grid, with a wall down the middle that leaves a gap at the top.
+    fn grid(wall_top: f32) -> NavGrid {
+        NavGrid::new(
+            Vec2::ZERO,
+            Vec2::splat(96.0),
+            &[(Vec2::new(44.0, 0.0), Vec2::new(52.0, wall_top))],
+        )
+    }
+
+    #[test]
+    fn walks_straight_across_open_ground() {
+        let grid = NavGrid::new(Vec2::ZERO, Vec2::splat(96.0), &[]);
+        let to = Vec2::new(84.0, 12.0);
+
+        assert_eq!(grid.find_path(Vec2::new(12.0, 12.0), to), Some(vec![to]));
+    }
+
+    #[test]
+    fn walks_around_obstacles() {
+        let grid = grid(64.0);
+        let from = Vec2::new(12.0, 12.0);
+        let to = Vec2::new(84.0, 12.0);
+
+        let path = grid.find_path(from, to).unwrap();
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.iter().any(|point| point.y > 70.0));
+        let mut previous = from;
+        for point in path {
+            assert!(grid.in_sight(previous, point));
+            previous = point;
+        }
+    }
+
+    #[test]
+    fn heads_for_the_closest_open_place_to_blocked_destinations() {
+        let grid = grid(64.0);
+        let to = Vec2::new(48.0, 12.0);
+
+        let end = *grid
+            .find_path(Vec2::new(12.0, 12.0), to)
+            .unwrap()
+            .last()
+            .unwrap();
+        assert_ne!(end, to);
+        assert!(!grid.blocked[grid.cell(end)]);
+        assert!(end.distance(to) <= CELL_SIZE * 2.0);
+    }
+
+    #[test]
+    fn gives_up_when_there_is_no_way_through() {
+        let grid = grid(200.0);
+
+        assert_eq!(
+            grid.find_path(Vec2::new(12.0, 12.0), Vec2::new(84.0, 12.0)),
+            None
+        );
+    }
+}
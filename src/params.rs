@@ -0,0 +1,28 @@
+//! Launch parameters, read from the command line on native and the URL query string on the web.
+
+/// Returns the value of a `--name value` command line argument.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get(name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+    }
+    None
+}
+
+/// Returns the value of a `name=value` URL query parameter.
+#[cfg(target_arch = "wasm32")]
+pub fn get(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    search.trim_start_matches('?').split('&').find_map(|pair| {
+        let mut parts = pair.splitn(2, '=');
+        if parts.next()? == name {
+            Some(parts.next().unwrap_or_default().to_string())
+        } else {
+            None
+        }
+    })
+}
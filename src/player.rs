@@ -1,4 +1,10 @@
+use crate::game_camera::CameraTarget;
+use crate::input::{Action, Coop, Movement};
+use crate::interactable::InRange;
 use crate::map;
+use crate::nav::WalkPath;
+use crate::preload::Preload;
+use crate::state::AppState;
 use crate::z::ZSync;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
@@ -16,15 +22,29 @@ const SPRITE_SHEET: &str = "textures/player.png";
 pub const SPRITE_WIDTH: f32 = 12.0;
 pub const SPRITE_HEIGHT: f32 = 23.0;
 pub const SPRITE_SHEET_PADDING: f32 = 1.0;
+/// Size of the collider the player walks on
+pub const FEET_RADIUS: f32 = SPRITE_WIDTH / 2.0;
+/// Height of the centre of the feet above the middle of the player
+pub const FEET_OFFSET: f32 = (SPRITE_WIDTH - SPRITE_HEIGHT) / 2.0;
 
 const STEP_DURATION_SECONDS: f32 = 0.15;
 
+/// How far to the side of the first player the second player joins
+pub const JOIN_OFFSET: f32 = SPRITE_WIDTH * 2.0;
+/// Tint that tells the second player apart from the first
+pub const SECOND_PLAYER_COLOR: Color = Color::rgb(0.7, 0.85, 1.0);
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(setup.system())
-            .add_system(system.system());
+        app.add_startup_system(preload.system())
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(setup.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(join.system())
+                    .with_system(system.system()),
+            );
     }
 }
 
@@ -36,14 +56,68 @@ pub struct StepTimer(Timer);
 #[derive(Default)]
 pub struct Player;
 
+/// Marks the player in local co-op who's moved by `Coop` instead of `Movement`. Queries for
+/// `Player` match both players.
+pub struct SecondPlayer;
+
+fn preload(asset_server: Res<AssetServer>, mut preload: ResMut<Preload>) {
+    preload.load(&asset_server, SPRITE_SHEET);
+}
+
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    bounds: Res<map::MapBounds>,
 ) {
+    spawn(
+        &mut commands,
+        &asset_server,
+        &mut texture_atlases,
+        bounds.centre(),
+        Color::WHITE,
+    );
+}
+
+/// Spawns the second player next to the first once they use their controls.
+fn join(
+    mut commands: Commands,
+    coop: Res<Coop>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    first_player_query: Query<&Transform, (With<Player>, Without<SecondPlayer>)>,
+    second_player_query: Query<(), With<SecondPlayer>>,
+) {
+    if second_player_query.iter().next().is_some()
+        || (coop.movement == Vec2::ZERO && !coop.actions.just_pressed(Action::Interact))
+    {
+        return;
+    }
+
+    if let Some(transform) = first_player_query.iter().next() {
+        info!("Second player joined");
+        let position = transform.translation.truncate() + Vec2::new(JOIN_OFFSET, 0.0);
+        let entity = spawn(
+            &mut commands,
+            &asset_server,
+            &mut texture_atlases,
+            position,
+            SECOND_PLAYER_COLOR,
+        );
+        commands.entity(entity).insert(SecondPlayer);
+    }
+}
+
+fn spawn(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    position: Vec2,
+    color: Color,
+) -> Entity {
     commands
         .spawn_bundle(RigidBodyBundle {
-            position: Vec3::new(map::MAP_WIDTH / 2.0, map::MAP_HEIGHT / 2.0, 0.0).into(),
+            position: position.into(),
             mass_properties: RigidBodyMassProps {
                 flags: RigidBodyMassPropsFlags::ROTATION_LOCKED,
                 ..Default::default()
@@ -61,8 +135,11 @@ fn setup(
             ..Default::default()
         })
         .insert(ClickStart::default())
+        .insert(WalkPath::default())
         .insert(ColliderPositionSync::Discrete)
-        .insert(Player::default())
+        .insert(Player)
+        .insert(InRange::default())
+        .insert(CameraTarget)
         .with_children(|parent| {
             parent
                 .spawn_bundle(SpriteSheetBundle {
@@ -75,6 +152,10 @@ fn setup(
                         STEPS as usize,
                         4,
                     )),
+                    sprite: TextureAtlasSprite {
+                        color,
+                        ..Default::default()
+                    },
                     transform: Transform {
                         translation: Vec3::ZERO,
                         ..Default::default()
@@ -85,57 +166,51 @@ fn setup(
                 .insert(ZSync(-SPRITE_HEIGHT / 2.0));
             // This collider is used for collision when walking
             parent.spawn_bundle(ColliderBundle {
-                shape: ColliderShape::ball(SPRITE_WIDTH / 2.0),
-                position: Vec2::new(0.0, (SPRITE_WIDTH - SPRITE_HEIGHT) / 2.0).into(),
+                shape: ColliderShape::ball(FEET_RADIUS),
+                position: Vec2::new(0.0, FEET_OFFSET).into(),
                 ..Default::default()
             });
-        });
+        })
+        .id()
 }
 
+#[allow(clippy::type_complexity)]
 pub fn system(
     windows: Res<Windows>,
     time: Res<Time>,
     mouse_buttons: Res<Input<MouseButton>>,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&mut ClickStart, &mut RigidBodyVelocity, &Children)>,
+    movement: Res<Movement>,
+    coop: Res<Coop>,
+    mut query: Query<(
+        &mut ClickStart,
+        &mut WalkPath,
+        &Transform,
+        &mut RigidBodyVelocity,
+        &Children,
+        Option<&SecondPlayer>,
+    )>,
     mut children_query: Query<(&mut StepTimer, &mut TextureAtlasSprite)>,
 ) {
     if let Some(window) = windows.get_primary() {
-        for (mut click_start, mut rigid_body_velocity, children) in query.iter_mut() {
+        for (
+            mut click_start,
+            mut walk_path,
+            transform,
+            mut rigid_body_velocity,
+            children,
+            second_player,
+        ) in query.iter_mut()
+        {
             for &child in children.iter() {
                 if let Ok((mut timer, mut sprite)) = children_query.get_mut(child) {
-                    let mut velocity = Vec2::ZERO;
-
-                    // get relevant keyboard presses
-                    let up =
-                        keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up);
-                    let down =
-                        keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down);
-                    let left =
-                        keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left);
-                    let right = keyboard_input.pressed(KeyCode::D)
-                        || keyboard_input.pressed(KeyCode::Right);
-
-                    // if keyboard moving horizonally
-                    if left ^ right {
-                        if left {
-                            velocity -= Vec2::X;
-                        } else {
-                            velocity += Vec2::X;
-                        }
-                    }
+                    // keys, the d-pad or the left stick
+                    let mut velocity = match second_player {
+                        Some(_) => coop.movement,
+                        None => movement.0,
+                    };
 
-                    // if keyboard moving vertically
-                    if up ^ down {
-                        if down {
-                            velocity -= Vec2::Y;
-                        } else {
-                            velocity += Vec2::Y;
-                        }
-                    }
-
-                    // no input from keyboard, try mouse
-                    if velocity == Vec2::ZERO {
+                    // no input from keyboard or gamepad, try mouse for the first player
+                    if velocity == Vec2::ZERO && second_player.is_none() {
                         if let Some(pos) = window.cursor_position() {
                             // Update start click position
                             if mouse_buttons.just_pressed(MouseButton::Left) {
@@ -157,6 +232,14 @@ pub fn system(
                         }
                     }
 
+                    // no other input, follow the path to where the player clicked
+                    if velocity == Vec2::ZERO {
+                        let feet = transform.translation.truncate() + Vec2::new(0.0, FEET_OFFSET);
+                        velocity = walk_path.direction(feet);
+                    } else {
+                        walk_path.clear();
+                    }
+
                     // clamp to 1
                     if velocity.length() > 1.0 {
                         velocity /= velocity.length()
@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+
+use crate::state::{self, AppState, ScreenAssets};
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+
+const BAR_WIDTH: f32 = 320.0;
+const BAR_HEIGHT: f32 = 16.0;
+const BAR_MARGIN: f32 = 16.0;
+
+/// Every asset the game needs, which are loaded before the world is spawned. Holding their handles
+/// keeps them loaded, so nothing pops in once the game has started.
+#[derive(Default)]
+pub struct Preload {
+    handles: Vec<(String, HandleUntyped)>,
+    /// Plugins that are still finding assets to preload, from data in the assets they've preloaded
+    discovering: HashSet<&'static str>,
+}
+
+impl Preload {
+    /// Starts loading an asset, unless it's already being preloaded.
+    pub fn load(&mut self, asset_server: &AssetServer, path: &str) {
+        if !self.handles.iter().any(|(loading, _)| loading == path) {
+            self.handles
+                .push((path.to_string(), asset_server.load_untyped(path)));
+        }
+    }
+
+    /// Keeps the game loading until `discovered` is called with the same name.
+    pub fn discovering(&mut self, name: &'static str) {
+        self.discovering.insert(name);
+    }
+
+    pub fn discovered(&mut self, name: &'static str) {
+        self.discovering.remove(name);
+    }
+
+    fn load_state(&self, asset_server: &AssetServer) -> LoadState {
+        match asset_server.get_group_load_state(self.handles.iter().map(|(_, handle)| handle.id)) {
+            LoadState::Loaded if !self.discovering.is_empty() => LoadState::Loading,
+            load_state => load_state,
+        }
+    }
+
+    /// The fraction of the assets found so far that have loaded.
+    fn progress(&self, asset_server: &AssetServer) -> f32 {
+        let loaded = self
+            .handles
+            .iter()
+            .filter(|(_, handle)| asset_server.get_load_state(handle) == LoadState::Loaded)
+            .count();
+        loaded as f32 / self.handles.len().max(1) as f32
+    }
+
+    fn failed(&self, asset_server: &AssetServer) -> Option<&str> {
+        self.handles
+            .iter()
+            .find(|(_, handle)| asset_server.get_load_state(handle) == LoadState::Failed)
+            .map(|(path, _)| path.as_str())
+    }
+}
+
+/// The part of the progress bar that fills up as assets load.
+struct ProgressBar;
+
+pub struct PreloadPlugin;
+
+impl Plugin for PreloadPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Preload>()
+            .add_system_set(
+                SystemSet::on_enter(AppState::Loading).with_system(show_progress.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Loading).with_system(wait_for_assets.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::LoadFailed).with_system(show_error.system()),
+            );
+    }
+}
+
+fn show_progress(
+    mut commands: Commands,
+    assets: Res<ScreenAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let screen = state::spawn_screen(&mut commands, &assets, &[("Loading...", state::FONT_SIZE)]);
+
+    let bar = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                margin: Rect {
+                    top: Val::Px(BAR_MARGIN),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: materials.add(Color::DARK_GRAY.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::WHITE.into()),
+                    ..Default::default()
+                })
+                .insert(ProgressBar);
+        })
+        .id();
+    commands.entity(screen).push_children(&[bar]);
+}
+
+fn wait_for_assets(
+    asset_server: Res<AssetServer>,
+    preload: Res<Preload>,
+    mut state: ResMut<State<AppState>>,
+    mut bar_query: Query<&mut Style, With<ProgressBar>>,
+) {
+    for mut style in bar_query.iter_mut() {
+        style.size.width = Val::Percent(preload.progress(&asset_server) * 100.0);
+    }
+
+    match preload.load_state(&asset_server) {
+        LoadState::Loaded => state.set(AppState::MainMenu).unwrap(),
+        LoadState::Failed => state.set(AppState::LoadFailed).unwrap(),
+        LoadState::NotLoaded | LoadState::Loading => {}
+    }
+}
+
+fn show_error(
+    mut commands: Commands,
+    assets: Res<ScreenAssets>,
+    asset_server: Res<AssetServer>,
+    preload: Res<Preload>,
+) {
+    let path = preload.failed(&asset_server).unwrap_or_default();
+    error!("Couldn't load {}", path);
+
+    state::spawn_screen(
+        &mut commands,
+        &assets,
+        &[
+            ("Couldn't load the game", state::TITLE_FONT_SIZE),
+            (&format!("{} is missing or broken", path), state::FONT_SIZE),
+            ("Try reloading", state::FONT_SIZE),
+        ],
+    );
+}
@@ -0,0 +1,374 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::door::UseDoor;
+use crate::input::Action;
+use crate::map::{self, Interiors, LoadMap, Location, MapBuilt, MapIds, MapSeed, MapSource};
+use crate::player::{self, Player, SecondPlayer};
+use crate::state::AppState;
+use crate::storage;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Bump this whenever `SaveData` changes in a way older saves can't be read as, and teach
+/// `migrate` to upgrade saves from the previous version.
+const SAVE_VERSION: u32 = 1;
+const SAVE_NAME: &str = "save";
+
+/// Frames to wait for the door the player was inside to be spawned, before giving up
+const DOOR_WAIT_FRAMES: u32 = 60;
+
+/// Progress through the game that's kept in saves.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Progress {
+    /// IDs of the doors whose interiors the player has been inside
+    pub visited: BTreeSet<String>,
+    pub inventory: Vec<String>,
+    pub flags: BTreeMap<String, bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SaveData {
+    version: u32,
+    map: SavedMap,
+    location: SavedLocation,
+    player: SavedPlayer,
+    #[serde(default)]
+    progress: Progress,
+}
+
+#[derive(Deserialize, Serialize)]
+enum SavedMap {
+    File(String),
+    Random(u64),
+    Endless(u64),
+}
+
+#[derive(Deserialize, Serialize)]
+enum SavedLocation {
+    Outdoors,
+    Inside {
+        door: String,
+        /// Where the player comes out of the interior
+        outside: Vec2,
+    },
+}
+
+#[derive(Deserialize, Serialize)]
+struct SavedPlayer {
+    /// Relative to the bottom left of the interior when inside one
+    position: Vec2,
+    /// Sprite the player is showing, which is the way they're facing
+    facing: u32,
+}
+
+/// Just enough of a save to tell which version it is.
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+/// A save being loaded, which takes a few frames while the world is rebuilt.
+enum PendingLoad {
+    Rebuilding(SaveData),
+    EnteringInterior {
+        save: SaveData,
+        door: String,
+        frames: u32,
+    },
+}
+
+#[derive(Default)]
+struct Loading(Option<PendingLoad>);
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Progress>()
+            .init_resource::<Loading>()
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(save.system())
+                    .with_system(load.system())
+                    .with_system(finish_loading.system())
+                    .with_system(track_visits.system()),
+            );
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn save(
+    actions: Res<Input<Action>>,
+    map_source: Res<MapSource>,
+    seed: Res<MapSeed>,
+    location: Res<Location>,
+    interiors: Res<Interiors>,
+    progress: Res<Progress>,
+    player_query: Query<(&Transform, &Children), (With<Player>, Without<SecondPlayer>)>,
+    sprite_query: Query<&TextureAtlasSprite>,
+) {
+    if !actions.just_pressed(Action::Save) {
+        return;
+    }
+
+    let (transform, children) = match player_query.iter().next() {
+        Some(player) => player,
+        None => return,
+    };
+    let mut position = transform.translation.truncate();
+    let facing = children
+        .iter()
+        .find_map(|child| sprite_query.get(*child).ok())
+        .map_or(0, |sprite| sprite.index);
+
+    let location = match &*location {
+        Location::Outdoors => SavedLocation::Outdoors,
+        Location::Inside(door) => match (interiors.origin(door), interiors.outside(door)) {
+            (Some(origin), Some(outside)) => {
+                position -= origin;
+                SavedLocation::Inside {
+                    door: door.clone(),
+                    outside,
+                }
+            }
+            _ => return,
+        },
+        // Wait until the player is through the door
+        Location::Entering(_) => return,
+    };
+
+    let save = SaveData {
+        version: SAVE_VERSION,
+        map: match &*map_source {
+            MapSource::File(path) => SavedMap::File(path.clone()),
+            MapSource::Random => SavedMap::Random(seed.0),
+            MapSource::Endless => SavedMap::Endless(seed.0),
+        },
+        location,
+        player: SavedPlayer { position, facing },
+        progress: progress.clone(),
+    };
+
+    let result = ron::ser::to_string_pretty(&save, Default::default())
+        .map_err(|error| error.to_string())
+        .and_then(|contents| storage::write(SAVE_NAME, &contents));
+    match result {
+        Ok(()) => info!("Saved the game"),
+        Err(error) => warn!("Couldn't save the game: {}", error),
+    }
+}
+
+fn load(
+    actions: Res<Input<Action>>,
+    mut loading: ResMut<Loading>,
+    mut load_map: EventWriter<LoadMap>,
+) {
+    if !actions.just_pressed(Action::Load) {
+        return;
+    }
+
+    let save = match storage::read(SAVE_NAME).and_then(|contents| match contents {
+        Some(contents) => parse(&contents),
+        None => Err("there's no saved game".to_string()),
+    }) {
+        Ok(save) => save,
+        Err(error) => {
+            warn!("Couldn't load the game: {}", error);
+            return;
+        }
+    };
+
+    load_map.send(match &save.map {
+        SavedMap::File(path) => LoadMap {
+            source: MapSource::File(path.clone()),
+            seed: 0,
+        },
+        SavedMap::Random(seed) => LoadMap {
+            source: MapSource::Random,
+            seed: *seed,
+        },
+        SavedMap::Endless(seed) => LoadMap {
+            source: MapSource::Endless,
+            seed: *seed,
+        },
+    });
+    loading.0 = Some(PendingLoad::Rebuilding(save));
+}
+
+/// Puts the player back where they were once the world has been rebuilt, going back through the
+/// door they were inside. The second player comes back beside the first, like when they join.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn finish_loading(
+    mut loading: ResMut<Loading>,
+    mut map_built: EventReader<MapBuilt>,
+    mut use_door: EventWriter<UseDoor>,
+    mut progress: ResMut<Progress>,
+    location: Res<Location>,
+    interiors: Res<Interiors>,
+    ids: Res<MapIds>,
+    mut player_query: Query<
+        (&mut RigidBodyPosition, &Children, Option<&SecondPlayer>),
+        With<Player>,
+    >,
+    mut sprite_query: Query<&mut TextureAtlasSprite>,
+) {
+    let built = map_built.iter().count() > 0;
+
+    loading.0 = match loading.0.take() {
+        Some(PendingLoad::Rebuilding(save)) if built => {
+            let position = match &save.location {
+                SavedLocation::Outdoors => save.player.position,
+                SavedLocation::Inside { outside, .. } => *outside,
+            };
+            for (mut rigid_body_position, children, second_player) in player_query.iter_mut() {
+                map::teleport(
+                    &mut rigid_body_position,
+                    beside(position, second_player.is_some()),
+                );
+                for child in children.iter() {
+                    if let Ok(mut sprite) = sprite_query.get_mut(*child) {
+                        sprite.index = save.player.facing;
+                    }
+                }
+            }
+            *progress = save.progress.clone();
+
+            match &save.location {
+                SavedLocation::Outdoors => {
+                    info!("Loaded the game");
+                    None
+                }
+                SavedLocation::Inside { door, .. } => Some(PendingLoad::EnteringInterior {
+                    door: door.clone(),
+                    save,
+                    frames: 0,
+                }),
+            }
+        }
+        Some(PendingLoad::EnteringInterior { save, door, frames }) => {
+            match &*location {
+                Location::Inside(id) if *id == door => {
+                    if let Some(origin) = interiors.origin(&door) {
+                        let position = origin + save.player.position;
+                        for (mut rigid_body_position, _, second_player) in player_query.iter_mut() {
+                            map::teleport(
+                                &mut rigid_body_position,
+                                beside(position, second_player.is_some()),
+                            );
+                        }
+                    }
+                    info!("Loaded the game");
+                    None
+                }
+                Location::Outdoors if frames >= DOOR_WAIT_FRAMES => {
+                    warn!("Couldn't find door {}, so loaded the game outside", door);
+                    None
+                }
+                Location::Outdoors => {
+                    // Doors in an endless world only exist once their chunk has been streamed in
+                    if ids.get(&door).is_some() {
+                        use_door.send(UseDoor(door.clone()));
+                    }
+                    Some(PendingLoad::EnteringInterior {
+                        save,
+                        door,
+                        frames: frames + 1,
+                    })
+                }
+                _ => Some(PendingLoad::EnteringInterior { save, door, frames }),
+            }
+        }
+        other => other,
+    };
+}
+
+/// Where a player goes when the first player goes to `position`, so the players don't overlap.
+fn beside(position: Vec2, second_player: bool) -> Vec2 {
+    if second_player {
+        position + Vec2::new(player::JOIN_OFFSET, 0.0)
+    } else {
+        position
+    }
+}
+
+fn track_visits(location: Res<Location>, mut progress: ResMut<Progress>) {
+    if let Location::Inside(door) = &*location {
+        if location.is_changed() && !progress.visited.contains(door) {
+            progress.visited.insert(door.clone());
+        }
+    }
+}
+
+/// Reads a save, upgrading it if it was made by an older version of the game.
+fn parse(contents: &str) -> Result<SaveData, String> {
+    let Version { version } = ron::from_str(contents).map_err(|error| error.to_string())?;
+
+    if version > SAVE_VERSION {
+        return Err(format!(
+            "the save is from a newer version of the game (version {})",
+            version
+        ));
+    }
+
+    migrate(version, contents)
+}
+
+/// Upgrades a save one version at a time, until it's the current version. When `SaveData`
+/// changes, keep the old layout as `SaveDataV<n>`, deserialize it here and convert it.
+fn migrate(version: u32, contents: &str) -> Result<SaveData, String> {
+    match version {
+        SAVE_VERSION => ron::from_str(contents).map_err(|error| error.to_string()),
+        _ => Err(format!("unknown save version {}", version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_saves_from_this_version() {
+        let save = SaveData {
+            version: SAVE_VERSION,
+            map: SavedMap::Random(3),
+            location: SavedLocation::Outdoors,
+            player: SavedPlayer {
+                position: Vec2::new(1.0, 2.0),
+                facing: 4,
+            },
+            progress: Progress::default(),
+        };
+
+        let save = parse(&ron::to_string(&save).unwrap()).unwrap();
+        assert!(matches!(save.map, SavedMap::Random(3)));
+        assert_eq!(save.player.position, Vec2::new(1.0, 2.0));
+        assert_eq!(save.player.facing, 4);
+    }
+
+    #[test]
+    fn progress_left_out_starts_empty() {
+        let save = parse(
+            "(
+                version: 1,
+                map: File(\"map/village.map.ron\"),
+                location: Outdoors,
+                player: (position: (0.0, 0.0), facing: 0),
+            )",
+        )
+        .unwrap();
+
+        assert!(save.progress.visited.is_empty());
+    }
+
+    #[test]
+    fn refuses_saves_it_cant_upgrade() {
+        let newer = parse(&format!("(version: {})", SAVE_VERSION + 1))
+            .err()
+            .unwrap();
+        assert!(newer.contains("newer version"));
+        assert!(parse("(version: 0)").is_err());
+        assert!(parse("not a save").is_err());
+    }
+}
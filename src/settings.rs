@@ -0,0 +1,122 @@
+use crate::game_camera::Scaling;
+use crate::input::{self, Bindings};
+use crate::storage;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const SETTINGS_NAME: &str = "settings";
+
+/// Player preferences, read from `settings.ron` in the data directory on native, or
+/// `localStorage` on the web. Settings left out of the file keep their defaults.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub bindings: Bindings<KeyCode>,
+    pub gamepad_bindings: Bindings<GamepadButtonType>,
+    /// Keys for the second player in local co-op
+    #[serde(
+        default = "Bindings::second_player",
+        deserialize_with = "input::deserialize_second_player"
+    )]
+    pub coop_bindings: Bindings<KeyCode>,
+    /// Which gamepad the second player uses, counting from 0 in the order they were connected.
+    /// The others control the first player.
+    pub coop_gamepad: usize,
+    pub stick_deadzone: f32,
+    pub scaling: Scaling,
+    /// Size of the world shown in pixel perfect mode, before zooming
+    pub internal_resolution: Vec2,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            bindings: Bindings::default(),
+            gamepad_bindings: Bindings::default(),
+            coop_bindings: Bindings::second_player(),
+            coop_gamepad: 1,
+            stick_deadzone: input::DEFAULT_STICK_DEADZONE,
+            scaling: Scaling::Smooth,
+            internal_resolution: Vec2::new(384.0, 216.0),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings, writing out the defaults to edit if there aren't any yet.
+    fn load() -> Self {
+        match storage::read(SETTINGS_NAME) {
+            Ok(Some(contents)) => match ron::from_str(&contents) {
+                Ok(settings) => return settings,
+                Err(error) => warn!(
+                    "Couldn't read the settings, so using the defaults: {}",
+                    error
+                ),
+            },
+            Ok(None) => {
+                let result = ron::ser::to_string_pretty(&Settings::default(), Default::default())
+                    .map_err(|error| error.to_string())
+                    .and_then(|contents| storage::write(SETTINGS_NAME, &contents));
+                if let Err(error) = result {
+                    warn!("Couldn't write the default settings: {}", error);
+                }
+            }
+            Err(error) => warn!(
+                "Couldn't read the settings, so using the defaults: {}",
+                error
+            ),
+        }
+
+        Settings::default()
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Settings::load());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+
+    #[test]
+    fn actions_left_out_keep_each_players_defaults() {
+        let settings: Settings =
+            ron::from_str("(bindings: (interact: [Space]), coop_bindings: (interact: [P]))")
+                .unwrap();
+
+        assert_eq!(settings.bindings.get(Action::Interact), &[KeyCode::Space]);
+        assert_eq!(
+            settings.bindings.get(Action::MoveUp),
+            &[KeyCode::W, KeyCode::Up]
+        );
+        assert_eq!(settings.coop_bindings.get(Action::Interact), &[KeyCode::P]);
+        assert_eq!(settings.coop_bindings.get(Action::MoveUp), &[KeyCode::I]);
+        assert!(settings.coop_bindings.get(Action::Pause).is_empty());
+    }
+
+    #[test]
+    fn settings_left_out_keep_their_defaults() {
+        let settings: Settings = ron::from_str("()").unwrap();
+
+        assert_eq!(settings.coop_bindings.get(Action::MoveLeft), &[KeyCode::J]);
+        assert_eq!(settings.coop_gamepad, 1);
+    }
+
+    #[test]
+    fn default_settings_read_back_the_same() {
+        let written = ron::ser::to_string_pretty(&Settings::default(), Default::default()).unwrap();
+        let settings: Settings = ron::from_str(&written).unwrap();
+
+        assert_eq!(settings.coop_bindings.get(Action::Interact), &[KeyCode::O]);
+        assert_eq!(
+            settings.gamepad_bindings.get(Action::Interact),
+            &[GamepadButtonType::South]
+        );
+    }
+}
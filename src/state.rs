@@ -0,0 +1,193 @@
+use crate::input::Action;
+use crate::settings::Settings;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+const FONT: &str = "fonts/monogram.ttf";
+pub const TITLE_FONT_SIZE: f32 = 64.0;
+pub const FONT_SIZE: f32 = 32.0;
+/// How much the screens shown over the game darken it
+const BACKGROUND_ALPHA: f32 = 0.6;
+
+const START_KEYS: [KeyCode; 2] = [KeyCode::Return, KeyCode::Space];
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AppState {
+    Loading,
+    /// An asset the game needs couldn't be loaded
+    LoadFailed,
+    MainMenu,
+    Playing,
+    /// Pushed on top of `Playing`, so the game carries on where it was when popped
+    Paused,
+}
+
+pub struct ScreenAssets {
+    font: Handle<Font>,
+    background: Handle<ColorMaterial>,
+}
+
+/// The root of the UI shown for a state, which is removed when leaving it.
+struct Screen;
+
+pub struct StatePlugin;
+
+impl Plugin for StatePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_state(AppState::Loading)
+            .add_startup_system(setup.system())
+            .add_system_set(
+                SystemSet::on_exit(AppState::Loading).with_system(despawn_screen.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::MainMenu).with_system(show_main_menu.system()),
+            )
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(start.system()))
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(despawn_screen.system()),
+            )
+            .add_system_set(SystemSet::on_update(AppState::Playing).with_system(pause.system()))
+            .add_system_set(
+                SystemSet::on_enter(AppState::Paused)
+                    .with_system(show_paused.system())
+                    .with_system(stop_physics.system()),
+            )
+            .add_system_set(SystemSet::on_update(AppState::Paused).with_system(resume.system()))
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused)
+                    .with_system(despawn_screen.system())
+                    .with_system(start_physics.system()),
+            );
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    commands.insert_resource(ScreenAssets {
+        font: asset_server.load(FONT),
+        background: materials.add(Color::rgba(0.0, 0.0, 0.0, BACKGROUND_ALPHA).into()),
+    });
+}
+
+fn show_main_menu(mut commands: Commands, assets: Res<ScreenAssets>) {
+    spawn_screen(
+        &mut commands,
+        &assets,
+        &[
+            ("RPG2022", TITLE_FONT_SIZE),
+            ("Press Enter to start", FONT_SIZE),
+        ],
+    );
+}
+
+fn show_paused(mut commands: Commands, assets: Res<ScreenAssets>, settings: Res<Settings>) {
+    let resume = match settings.bindings.get(Action::Pause).first() {
+        Some(key) => format!("Press {:?} to resume", key),
+        None => String::new(),
+    };
+    spawn_screen(
+        &mut commands,
+        &assets,
+        &[("Paused", TITLE_FONT_SIZE), (&resume, FONT_SIZE)],
+    );
+}
+
+/// Starts the game on a key press, click or tap.
+fn start(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut mouse_buttons: ResMut<Input<MouseButton>>,
+    touches: Res<Touches>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let key = START_KEYS
+        .iter()
+        .find(|key| keyboard_input.just_pressed(**key))
+        .copied();
+    let clicked = mouse_buttons.just_pressed(MouseButton::Left);
+    if key.is_none() && !clicked && touches.iter_just_pressed().next().is_none() {
+        return;
+    }
+
+    // Don't let the press carry on into the game
+    if let Some(key) = key {
+        keyboard_input.reset(key);
+    }
+    mouse_buttons.reset(MouseButton::Left);
+
+    state.set(AppState::Playing).unwrap();
+}
+
+fn pause(mut actions: ResMut<Input<Action>>, mut state: ResMut<State<AppState>>) {
+    if actions.just_pressed(Action::Pause) {
+        // Otherwise the paused state would see the same press and resume straight away
+        actions.reset(Action::Pause);
+        state.push(AppState::Paused).unwrap();
+    }
+}
+
+fn resume(mut actions: ResMut<Input<Action>>, mut state: ResMut<State<AppState>>) {
+    if actions.just_pressed(Action::Pause) {
+        actions.reset(Action::Pause);
+        state.pop().unwrap();
+    }
+}
+
+fn stop_physics(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = false;
+}
+
+fn start_physics(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = true;
+}
+
+/// Spawns lines of text in the middle of the window, over a dark background. Returns the screen,
+/// so more can be added below the text.
+pub fn spawn_screen(
+    commands: &mut Commands,
+    assets: &ScreenAssets,
+    lines: &[(&str, f32)],
+) -> Entity {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                // Columns are laid out from the bottom up
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: assets.background.clone(),
+            ..Default::default()
+        })
+        .insert(Screen)
+        .with_children(|parent| {
+            for (text, font_size) in lines {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        *text,
+                        TextStyle {
+                            font: assets.font.clone(),
+                            font_size: *font_size,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+        })
+        .id()
+}
+
+fn despawn_screen(mut commands: Commands, screen_query: Query<Entity, With<Screen>>) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,63 @@
+//! Where saves and settings are kept: RON files in the user's data directory on native, and
+//! `localStorage` on the web.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    fn path(name: &str) -> Result<PathBuf, String> {
+        dirs::data_dir()
+            .map(|directory| directory.join("rpg2022").join(format!("{}.ron", name)))
+            .ok_or_else(|| "couldn't find a data directory".to_string())
+    }
+
+    pub fn write(name: &str, contents: &str) -> Result<(), String> {
+        let path = path(name)?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+        }
+        fs::write(&path, contents).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn read(name: &str) -> Result<Option<String>, String> {
+        let path = path(name)?;
+        match fs::read_to_string(&path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(format!("{}: {}", path.display(), error)),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    fn key(name: &str) -> String {
+        format!("rpg2022-{}", name)
+    }
+
+    fn local_storage() -> Result<web_sys::Storage, String> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| "localStorage isn't available".to_string())
+    }
+
+    pub fn write(name: &str, contents: &str) -> Result<(), String> {
+        local_storage()?
+            .set_item(&key(name), contents)
+            .map_err(|error| format!("{:?}", error))
+    }
+
+    pub fn read(name: &str) -> Result<Option<String>, String> {
+        local_storage()?
+            .get_item(&key(name))
+            .map_err(|error| format!("{:?}", error))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub use web::*;
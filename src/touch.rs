@@ -0,0 +1,272 @@
+use crate::input::ActionSystem;
+use crate::preload::Preload;
+use crate::state::AppState;
+use crate::window;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+const JOYSTICK_BASE: &str = "textures/ui/joystick_base.png";
+const JOYSTICK_KNOB: &str = "textures/ui/joystick_knob.png";
+const INTERACT_BUTTON: &str = "textures/ui/interact_button.png";
+const FONT: &str = "fonts/monogram.ttf";
+
+const JOYSTICK_SIZE: f32 = 128.0;
+const KNOB_SIZE: f32 = 48.0;
+/// How far the thumb moves from where it landed to walk at full speed
+const JOYSTICK_RADIUS: f32 = (JOYSTICK_SIZE - KNOB_SIZE) / 2.0;
+const BUTTON_SIZE: f32 = 72.0;
+/// Space between the interact button and the bottom right corner of the window
+const BUTTON_MARGIN: f32 = 32.0;
+const BUTTON_FONT_SIZE: f32 = 24.0;
+
+/// On-screen controls for touch screens. The first touch away from the interact button becomes a
+/// joystick centred where it landed. Two more touches pinch to zoom while the joystick keeps
+/// walking, as do two touches landing together, and any others are ignored until they're lifted.
+#[derive(Default)]
+pub struct TouchControls {
+    /// The touch moving the player, and where it landed
+    joystick: Option<(u64, Vec2)>,
+    /// The touch holding the interact button down
+    button: Option<u64>,
+    /// A touch that's landed while the joystick is held, waiting for another to pinch with
+    spare: Option<u64>,
+    /// The touches pinching
+    pinching: Option<(u64, u64)>,
+    /// The interact button is only shown once the screen has been touched
+    touched: bool,
+    /// The way the joystick is pushed, with a length of up to 1
+    pub movement: Vec2,
+    pub interact: bool,
+    /// How much the pinch has zoomed in since the last frame, as a multiple
+    pub pinch: Option<f32>,
+}
+
+struct JoystickBase;
+
+struct JoystickKnob;
+
+struct InteractButton;
+
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<TouchControls>()
+            .add_startup_system(preload.system())
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(setup.system()))
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::on_update(AppState::Playing).with_system(
+                    update_controls
+                        .system()
+                        .after(InputSystem)
+                        .before(ActionSystem),
+                ),
+            )
+            .add_system(show_controls.system());
+    }
+}
+
+fn preload(asset_server: Res<AssetServer>, mut preload: ResMut<Preload>) {
+    for path in [JOYSTICK_BASE, JOYSTICK_KNOB, INTERACT_BUTTON] {
+        preload.load(&asset_server, path);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let hidden = Visible {
+        is_visible: false,
+        is_transparent: true,
+    };
+    let absolute = |size| Style {
+        size: Size::new(Val::Px(size), Val::Px(size)),
+        position_type: PositionType::Absolute,
+        ..Default::default()
+    };
+
+    commands
+        .spawn_bundle(ImageBundle {
+            style: absolute(JOYSTICK_SIZE),
+            material: materials.add(asset_server.load(JOYSTICK_BASE).into()),
+            visible: hidden.clone(),
+            ..Default::default()
+        })
+        .insert(JoystickBase);
+    commands
+        .spawn_bundle(ImageBundle {
+            style: absolute(KNOB_SIZE),
+            material: materials.add(asset_server.load(JOYSTICK_KNOB).into()),
+            visible: hidden.clone(),
+            ..Default::default()
+        })
+        .insert(JoystickKnob);
+    commands
+        .spawn_bundle(ImageBundle {
+            style: Style {
+                position: Rect {
+                    right: Val::Px(BUTTON_MARGIN),
+                    bottom: Val::Px(BUTTON_MARGIN),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..absolute(BUTTON_SIZE)
+            },
+            material: materials.add(asset_server.load(INTERACT_BUTTON).into()),
+            visible: hidden,
+            ..Default::default()
+        })
+        .insert(InteractButton)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "Use",
+                        TextStyle {
+                            font: asset_server.load(FONT),
+                            font_size: BUTTON_FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    visible: Visible {
+                        is_visible: false,
+                        is_transparent: true,
+                    },
+                    ..Default::default()
+                })
+                .insert(InteractButton);
+        });
+}
+
+/// Works out which way the joystick is pushed and whether the interact button is held, before
+/// the touches are read as actions.
+fn update_controls(
+    windows: Res<Windows>,
+    touches: Res<Touches>,
+    mut mouse_buttons: ResMut<Input<MouseButton>>,
+    mut controls: ResMut<TouchControls>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    // Browsers and some systems send a click for each tap too, which would steer the player by the
+    // mouse and click to move as well as using the touch controls
+    if touches.iter().next().is_some() || touches.iter_just_released().next().is_some() {
+        mouse_buttons.reset(MouseButton::Left);
+    }
+
+    // Let go of touches that have been lifted, even while the controls weren't being updated
+    let lifted = |id| touches.get_pressed(id).is_none();
+    if controls.joystick.is_some_and(|(id, _)| lifted(id)) {
+        controls.joystick = None;
+    }
+    if controls.button.is_some_and(lifted) {
+        controls.button = None;
+    }
+    if controls.spare.is_some_and(lifted) {
+        controls.spare = None;
+    }
+    if controls
+        .pinching
+        .is_some_and(|(a, b)| lifted(a) || lifted(b))
+    {
+        controls.pinching = None;
+    }
+
+    let button_centre = Vec2::new(
+        window.width() - BUTTON_MARGIN - BUTTON_SIZE / 2.0,
+        BUTTON_MARGIN + BUTTON_SIZE / 2.0,
+    );
+    let mut new_joystick = None;
+    for touch in touches.iter_just_pressed() {
+        controls.touched = true;
+        let position = window::touch_position(window, touch);
+
+        if position.distance(button_centre) <= BUTTON_SIZE / 2.0 {
+            controls.button.get_or_insert(touch.id());
+        } else if controls.pinching.is_some() {
+            continue;
+        } else if let Some(joystick) = new_joystick {
+            // Two fingers landing together pinch instead of walking
+            controls.pinching = Some((joystick, touch.id()));
+            controls.joystick = None;
+        } else if let Some(spare) = controls.spare {
+            controls.pinching = Some((spare, touch.id()));
+            controls.spare = None;
+        } else if controls.joystick.is_some() {
+            controls.spare = Some(touch.id());
+        } else {
+            controls.joystick = Some((touch.id(), position));
+            new_joystick = Some(touch.id());
+        }
+    }
+
+    controls.movement = controls
+        .joystick
+        .and_then(|(id, origin)| {
+            let touch = touches.get_pressed(id)?;
+            let offset = (window::touch_position(window, touch) - origin) / JOYSTICK_RADIUS;
+            Some(if offset.length() > 1.0 {
+                offset.normalize()
+            } else {
+                offset
+            })
+        })
+        .unwrap_or_default();
+    controls.interact = controls.button.is_some();
+    controls.pinch = controls.pinching.and_then(|(a, b)| {
+        let (a, b) = (touches.get_pressed(a)?, touches.get_pressed(b)?);
+        let previous = a.previous_position().distance(b.previous_position());
+        (previous > 0.0).then(|| a.position().distance(b.position()) / previous)
+    });
+}
+
+/// Shows the joystick under the thumb moving the player, and the interact button once the screen
+/// has been touched.
+#[allow(clippy::type_complexity)]
+fn show_controls(
+    controls: Res<TouchControls>,
+    state: Res<State<AppState>>,
+    mut query_set: QuerySet<(
+        Query<(&mut Style, &mut Visible), With<JoystickBase>>,
+        Query<(&mut Style, &mut Visible), With<JoystickKnob>>,
+        Query<&mut Visible, With<InteractButton>>,
+    )>,
+) {
+    let playing = *state.current() == AppState::Playing;
+    let origin = controls
+        .joystick
+        .filter(|_| playing)
+        .map(|(_, origin)| origin);
+
+    for (mut style, mut visible) in query_set.q0_mut().iter_mut() {
+        visible.is_visible = origin.is_some();
+        if let Some(origin) = origin {
+            style.position = centred_on(origin, JOYSTICK_SIZE);
+        }
+    }
+    for (mut style, mut visible) in query_set.q1_mut().iter_mut() {
+        visible.is_visible = origin.is_some();
+        if let Some(origin) = origin {
+            style.position = centred_on(origin + controls.movement * JOYSTICK_RADIUS, KNOB_SIZE);
+        }
+    }
+    for mut visible in query_set.q2_mut().iter_mut() {
+        visible.is_visible = controls.touched && playing;
+    }
+}
+
+fn centred_on(centre: Vec2, size: f32) -> Rect<Val> {
+    Rect {
+        left: Val::Px(centre.x - size / 2.0),
+        bottom: Val::Px(centre.y - size / 2.0),
+        ..Default::default()
+    }
+}
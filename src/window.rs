@@ -1,5 +1,24 @@
 #[cfg(target_arch = "wasm32")]
 mod web_fullscreen;
 
+#[cfg(target_arch = "wasm32")]
+mod web_touch;
+
 #[cfg(target_arch = "wasm32")]
 pub use web_fullscreen::*;
+#[cfg(target_arch = "wasm32")]
+pub use web_touch::*;
+
+use bevy::input::touch::Touch;
+use bevy::prelude::*;
+
+/// Converts a touch position to window coordinates with the origin at the bottom left, matching
+/// `Window::cursor_position`.
+pub fn touch_position(window: &Window, touch: &Touch) -> Vec2 {
+    let position = touch.position();
+    if cfg!(any(target_os = "android", target_os = "ios")) {
+        position
+    } else {
+        Vec2::new(position.x, window.height() - position.y)
+    }
+}
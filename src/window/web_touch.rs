@@ -0,0 +1,94 @@
+use bevy::input::touch::{TouchInput, TouchPhase};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use gloo_events::{EventListener, EventListenerOptions};
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Mutex,
+};
+use wasm_bindgen::JsCast;
+
+/// Sends the canvas's touches to `Touches`. Winit only sees pointer events in the browser, so
+/// touches never reach Bevy otherwise.
+pub struct WebTouchPlugin;
+
+impl Plugin for WebTouchPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let channel = std::sync::mpsc::channel();
+        let touch_sender: Sender<TouchInput> = channel.0;
+        let touch_receiver: Receiver<TouchInput> = channel.1;
+
+        app.insert_resource(Mutex::new(touch_sender))
+            .insert_resource(Mutex::new(touch_receiver))
+            .add_startup_system(setup_touch_listeners_system.system())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                touch_system.system().before(InputSystem),
+            );
+    }
+}
+
+fn setup_touch_listeners_system(touch_sender: Res<Mutex<Sender<TouchInput>>>) {
+    let canvas = match web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.query_selector("canvas").ok().flatten())
+    {
+        Some(canvas) => canvas,
+        None => {
+            warn!("Couldn't find the canvas, so touches won't be read");
+            return;
+        }
+    };
+
+    let sender = touch_sender.lock().unwrap();
+    for (event_type, phase) in [
+        ("touchstart", TouchPhase::Started),
+        ("touchmove", TouchPhase::Moved),
+        ("touchend", TouchPhase::Ended),
+        ("touchcancel", TouchPhase::Cancelled),
+    ] {
+        let local_sender = sender.clone();
+        let local_canvas = canvas.clone();
+        EventListener::new_with_options(
+            canvas.unchecked_ref(),
+            event_type,
+            EventListenerOptions::enable_prevent_default(),
+            move |event| {
+                let event = match event.dyn_ref::<web_sys::TouchEvent>() {
+                    Some(event) => event,
+                    None => return,
+                };
+                // Stops the browser scrolling, zooming and sending mouse events for the touch
+                event.prevent_default();
+
+                // Relative to the top left of the canvas, like winit's touches
+                let rect = local_canvas.get_bounding_client_rect();
+                let touches = event.changed_touches();
+                for touch in (0..touches.length()).filter_map(|index| touches.get(index)) {
+                    let position = Vec2::new(
+                        (touch.client_x() as f64 - rect.left()) as f32,
+                        (touch.client_y() as f64 - rect.top()) as f32,
+                    );
+                    local_sender
+                        .send(TouchInput {
+                            phase,
+                            position,
+                            force: None,
+                            id: touch.identifier() as u64,
+                        })
+                        .unwrap();
+                }
+            },
+        )
+        .forget();
+    }
+}
+
+fn touch_system(
+    touch_receiver: Res<Mutex<Receiver<TouchInput>>>,
+    mut touch_input: EventWriter<TouchInput>,
+) {
+    for touch in touch_receiver.lock().unwrap().try_iter() {
+        touch_input.send(touch);
+    }
+}